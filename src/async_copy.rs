@@ -0,0 +1,181 @@
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::{self, JoinError, JoinSet};
+
+use crate::pool::FileJob;
+use crate::shared::Shared;
+use crate::{
+    Context, CopyError, CopyFailure, CopyOptions, CopyStats, Jobs, Target, copy_one, finish_tree,
+    log_finished, recreate_symlink, resolve_target, sync_parent, walk_and_copy,
+};
+
+type JobResult = (PathBuf, Result<(), CopyError>, CopyStats);
+
+pub async fn copy_recursive_async(
+    src: &Path,
+    dst: &Path,
+    opts: &CopyOptions,
+) -> Result<CopyStats, CopyFailure> {
+    let failed = |error: CopyError| CopyFailure { stats: Box::default(), error };
+    let src = src.to_path_buf();
+    let opts = Arc::new(opts.clone());
+    let shared = Arc::new(Shared::new(&opts));
+
+    let target = blocking({
+        let (src, dst, opts) = (src.clone(), dst.to_path_buf(), opts.clone());
+        move || resolve_target(&src, &dst, &opts)
+    })
+    .await
+    .map_err(failed)?;
+    let stats = CopyStats::default();
+    let root = src.clone();
+
+    let stats = match target {
+        Target::File(dest_path) => {
+            blocking(move || {
+                let mut stats = CopyStats {
+                    destination: dest_path.clone(),
+                    ..stats
+                };
+                let res = copy_one(
+                    &src,
+                    &dest_path,
+                    &opts,
+                    &shared,
+                    &mut Vec::new(),
+                    &mut stats,
+                )
+                .and_then(|()| sync_parent(&opts, &dest_path));
+                CopyFailure::with_stats(stats, res)
+            })
+            .await
+        }
+        Target::Symlink(dest_path) => {
+            blocking(move || {
+                let mut stats = CopyStats {
+                    destination: dest_path.clone(),
+                    ..stats
+                };
+                let res = recreate_symlink(&src, &dest_path, None, &opts, &mut stats)
+                    .and_then(|()| sync_parent(&opts, &dest_path));
+                CopyFailure::with_stats(stats, res)
+            })
+            .await
+        }
+        Target::Dir(base_dst) => copy_dir(src, base_dst, opts, shared, stats).await,
+    }?;
+    log_finished(&root, dst, &stats);
+    Ok(stats)
+}
+
+async fn copy_dir(
+    src: PathBuf,
+    base_dst: PathBuf,
+    opts: Arc<CopyOptions>,
+    shared: Arc<Shared>,
+    stats: CopyStats,
+) -> Result<CopyStats, CopyFailure> {
+    let permits = opts.worker_threads();
+    let (sender, mut receiver) = mpsc::channel(permits * 4);
+
+    let walker = task::spawn_blocking({
+        let (src, opts, shared, base_dst) =
+            (src.clone(), opts.clone(), shared.clone(), base_dst.clone());
+        move || {
+            let mut ctx = Context::new(&opts, &shared, base_dst.clone(), stats);
+            ctx.jobs = Jobs::Async(sender);
+            let walked = walk_and_copy(&src, &base_dst, &mut ctx);
+            (walked, ctx.stats, ctx.dirs, ctx.links, ctx.filter)
+        }
+    });
+
+    // At most `permits` jobs run at once, so buffers handed back by finished
+    // jobs are enough for the ones that follow.
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    let semaphore = Arc::new(Semaphore::new(permits));
+    let mut tasks = JoinSet::new();
+    let mut job_stats = CopyStats::default();
+    let mut failure = None;
+
+    while let Some(job) = receiver.recv().await {
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        tasks.spawn_blocking({
+            let (opts, shared, buffers) = (opts.clone(), shared.clone(), buffers.clone());
+            move || {
+                let _permit = permit;
+                let FileJob { src, dst } = job;
+                let mut stats = CopyStats::default();
+                let mut buf = buffers
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .pop()
+                    .unwrap_or_default();
+                let res = copy_one(&src, &dst, &opts, &shared, &mut buf, &mut stats);
+                buffers.lock().unwrap_or_else(|e| e.into_inner()).push(buf);
+                (src, res, stats)
+            }
+        });
+
+        while let Some(done) = tasks.try_join_next() {
+            finish_job(done, &opts, &mut job_stats, &mut failure);
+        }
+        if failure.is_some() {
+            break;
+        }
+    }
+    drop(receiver);
+
+    while let Some(done) = tasks.join_next().await {
+        finish_job(done, &opts, &mut job_stats, &mut failure);
+    }
+
+    let (walked, mut stats, mut dirs, links, filter) = walker
+        .await
+        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()));
+    stats.merge(job_stats);
+    stats.destination = base_dst.clone();
+    let copied = walked.and_then(|()| failure.map_or(Ok(()), Err));
+    let stats = CopyFailure::with_stats(stats, copied)?;
+
+    blocking(move || {
+        let mut stats = stats;
+        let finished = finish_tree(&src, &base_dst, &opts, &filter, links, &mut dirs, &mut stats);
+        CopyFailure::with_stats(stats, finished)
+    })
+    .await
+}
+
+fn finish_job(
+    done: Result<JobResult, JoinError>,
+    opts: &CopyOptions,
+    stats: &mut CopyStats,
+    failure: &mut Option<CopyError>,
+) {
+    let (path, res, job_stats) = done.unwrap_or_else(|e| panic::resume_unwind(e.into_panic()));
+    stats.merge(job_stats);
+    match res {
+        Ok(()) => {}
+        Err(e) if opts.continue_on_error && !e.is_fatal() => stats.errors.push((path, e)),
+        Err(e) => {
+            failure.get_or_insert(e);
+        }
+    }
+}
+
+async fn blocking<T, E, F>(f: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+}
@@ -1,15 +1,134 @@
-use std::{io, path::PathBuf};
+use std::{fmt, io, path::PathBuf};
 use walkdir_minimal::WalkError;
 
+use crate::CopyStats;
+
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CopyError {
     Io(io::Error),
     Walk(WalkError),
     DepthExceeded(PathBuf),
-    SymlinkLoop(PathBuf),
+    SymlinkLoop { at: PathBuf, chain: Vec<PathBuf> },
     SrcNotFound(PathBuf),
     DestNotDir(PathBuf),
+    DestIsSymlink(PathBuf),
+    DestParentMissing(PathBuf),
+    CaseCollision { existing: PathBuf, new: PathBuf },
     NotSupported(PathBuf),
+    ReflinkNotSupported(PathBuf),
+    DestInsideSrc(PathBuf),
+    SameFile(PathBuf),
+    UnmappedOwner { path: PathBuf, uid: Option<u32>, gid: Option<u32> },
+    VerifyFailed { path: PathBuf, expected: String, found: String },
+    Aborted(PathBuf),
+    Cancelled,
+    TimedOut,
+    BudgetExceeded(PathBuf),
+}
+
+// The variant of a `CopyError` without its payload, so errors can be compared
+// and stored. I/O failures keep their `io::ErrorKind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CopyErrorKind {
+    Io(io::ErrorKind),
+    DepthExceeded,
+    SymlinkLoop,
+    SrcNotFound,
+    DestNotDir,
+    DestIsSymlink,
+    DestParentMissing,
+    CaseCollision,
+    NotSupported,
+    ReflinkNotSupported,
+    DestInsideSrc,
+    SameFile,
+    UnmappedOwner,
+    VerifyFailed,
+    Aborted,
+    Cancelled,
+    TimedOut,
+    BudgetExceeded,
+}
+
+impl CopyError {
+    // Walk errors are reported like their direct counterparts: an I/O error
+    // while reading a directory is `Io`, a loop found by the walker is
+    // `SymlinkLoop`.
+    pub fn kind(&self) -> CopyErrorKind {
+        match self {
+            CopyError::Io(e) | CopyError::Walk(WalkError::Io(e, _)) => CopyErrorKind::Io(e.kind()),
+            CopyError::Walk(WalkError::LoopDetected(_)) | CopyError::SymlinkLoop { .. } => CopyErrorKind::SymlinkLoop,
+            CopyError::DepthExceeded(_) => CopyErrorKind::DepthExceeded,
+            CopyError::SrcNotFound(_) => CopyErrorKind::SrcNotFound,
+            CopyError::DestNotDir(_) => CopyErrorKind::DestNotDir,
+            CopyError::DestIsSymlink(_) => CopyErrorKind::DestIsSymlink,
+            CopyError::DestParentMissing(_) => CopyErrorKind::DestParentMissing,
+            CopyError::CaseCollision { .. } => CopyErrorKind::CaseCollision,
+            CopyError::NotSupported(_) => CopyErrorKind::NotSupported,
+            CopyError::ReflinkNotSupported(_) => CopyErrorKind::ReflinkNotSupported,
+            CopyError::DestInsideSrc(_) => CopyErrorKind::DestInsideSrc,
+            CopyError::SameFile(_) => CopyErrorKind::SameFile,
+            CopyError::UnmappedOwner { .. } => CopyErrorKind::UnmappedOwner,
+            CopyError::VerifyFailed { .. } => CopyErrorKind::VerifyFailed,
+            CopyError::Aborted(_) => CopyErrorKind::Aborted,
+            CopyError::Cancelled => CopyErrorKind::Cancelled,
+            CopyError::TimedOut => CopyErrorKind::TimedOut,
+            CopyError::BudgetExceeded(_) => CopyErrorKind::BudgetExceeded,
+        }
+    }
+
+    // The `errno` behind an I/O error, which `io::ErrorKind` does not always
+    // tell apart (e.g. `ENOSPC` and `EDQUOT`).
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            CopyError::Io(e) | CopyError::Walk(WalkError::Io(e, _)) => e.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    // Errors that end the copy even with `continue_on_error`.
+    pub(crate) fn is_fatal(&self) -> bool {
+        matches!(self, CopyError::Aborted(_) | CopyError::Cancelled | CopyError::TimedOut | CopyError::BudgetExceeded(_))
+    }
+}
+
+// A failed copy together with what it did before stopping: the files and
+// bytes already copied, the errors collected with `continue_on_error`. The
+// stats are boxed to keep the `Err` side of a `Result` small.
+#[derive(Debug)]
+pub struct CopyFailure {
+    pub stats: Box<CopyStats>,
+    pub error: CopyError,
+}
+
+impl CopyFailure {
+    // Hands `stats` back either way, with the error if `res` failed.
+    pub(crate) fn with_stats(stats: CopyStats, res: Result<(), CopyError>) -> Result<CopyStats, CopyFailure> {
+        match res {
+            Ok(()) => Ok(stats),
+            Err(error) => Err(CopyFailure { stats: Box::new(stats), error }),
+        }
+    }
+}
+
+impl From<CopyFailure> for CopyError {
+    fn from(failure: CopyFailure) -> Self {
+        failure.error
+    }
+}
+
+impl fmt::Display for CopyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (after {} files, {} bytes)", self.error, self.stats.files_copied, self.stats.bytes_copied)
+    }
+}
+
+impl std::error::Error for CopyFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
 }
 
 impl From<io::Error> for CopyError {
@@ -17,3 +136,63 @@ impl From<io::Error> for CopyError {
         CopyError::Io(e)
     }
 }
+
+impl From<WalkError> for CopyError {
+    fn from(e: WalkError) -> Self {
+        CopyError::Walk(e)
+    }
+}
+
+impl fmt::Display for CopyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CopyError::Io(e) => write!(f, "I/O error: {}", e),
+            CopyError::Walk(e) => write!(f, "directory walk error: {}", e),
+            CopyError::DepthExceeded(p) => write!(f, "maximum depth exceeded at {}", p.display()),
+            CopyError::SymlinkLoop { at, chain } => {
+                write!(f, "symbolic link loop detected at {} (", at.display())?;
+                for path in chain {
+                    write!(f, "{} -> ", path.display())?;
+                }
+                write!(f, "{})", at.display())
+            }
+            CopyError::SrcNotFound(p) => write!(f, "source path not found: {}", p.display()),
+            CopyError::DestNotDir(p) => write!(f, "destination is not a directory: {}", p.display()),
+            CopyError::DestIsSymlink(p) => write!(f, "destination is a symbolic link: {}", p.display()),
+            CopyError::DestParentMissing(p) => write!(f, "destination parent does not exist: {}", p.display()),
+            CopyError::CaseCollision { existing, new } => {
+                write!(f, "{} and {} only differ in case at the destination", existing.display(), new.display())
+            }
+            CopyError::NotSupported(p) => write!(f, "unsupported file type: {}", p.display()),
+            CopyError::ReflinkNotSupported(p) => write!(f, "reflink not supported for {}", p.display()),
+            CopyError::DestInsideSrc(p) => write!(f, "destination is inside the source: {}", p.display()),
+            CopyError::SameFile(p) => write!(f, "source and destination are the same file: {}", p.display()),
+            CopyError::UnmappedOwner { path, uid, gid } => {
+                write!(f, "no owner mapping for {}", path.display())?;
+                match (uid, gid) {
+                    (Some(uid), Some(gid)) => write!(f, " (uid {}, gid {})", uid, gid),
+                    (Some(uid), None) => write!(f, " (uid {})", uid),
+                    (None, Some(gid)) => write!(f, " (gid {})", gid),
+                    (None, None) => Ok(()),
+                }
+            }
+            CopyError::VerifyFailed { path, expected, found } => {
+                write!(f, "verification failed for {}: expected {}, found {}", path.display(), expected, found)
+            }
+            CopyError::Aborted(p) => write!(f, "copy aborted at {}", p.display()),
+            CopyError::Cancelled => write!(f, "copy cancelled"),
+            CopyError::TimedOut => write!(f, "copy deadline passed"),
+            CopyError::BudgetExceeded(p) => write!(f, "total size limit reached before {}", p.display()),
+        }
+    }
+}
+
+impl std::error::Error for CopyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CopyError::Io(e) => Some(e),
+            CopyError::Walk(e) => Some(e),
+            _ => None,
+        }
+    }
+}
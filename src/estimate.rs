@@ -0,0 +1,45 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::sink::{CopySink, copy_recursive_to_sink};
+use crate::{CopyError, CopyOptions};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyEstimate {
+    pub total_files: u64,
+    pub total_dirs: u64,
+    pub total_bytes: u64,
+}
+
+// Discards everything; the walk's own stats already count what a copy with
+// the same options would write.
+struct Discard;
+
+impl CopySink for Discard {
+    fn on_dir(&mut self, _path: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_file(
+        &mut self,
+        _path: &Path,
+        _meta: &fs::Metadata,
+        _contents: &mut dyn Read,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_symlink(&mut self, _path: &Path, _target: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn estimate(src: &Path, opts: &CopyOptions) -> Result<CopyEstimate, CopyError> {
+    let stats = copy_recursive_to_sink(src, &mut Discard, opts)?;
+    Ok(CopyEstimate {
+        total_files: stats.files_copied,
+        total_dirs: stats.dirs_created,
+        total_bytes: stats.bytes_copied,
+    })
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::CopyOptions;
+
+pub(crate) const IGNORE_FILE: &str = ".copyignore";
+
+struct Pattern {
+    glob: Vec<u8>,
+    full_path: bool,
+}
+
+impl Pattern {
+    // Patterns are bytes like the names they are matched against, so ignore
+    // files can name files that are not valid UTF-8.
+    fn new(pattern: &[u8]) -> Self {
+        let end = pattern.iter().rposition(|&b| b != b'/').map_or(0, |i| i + 1);
+        let trimmed = &pattern[..end];
+        let anchored = trimmed.strip_prefix(b"/");
+        let glob = anchored.unwrap_or(trimmed);
+        Self {
+            glob: glob.to_vec(),
+            full_path: anchored.is_some() || glob.contains(&b'/'),
+        }
+    }
+
+    fn matches(&self, rel: &Path) -> bool {
+        if self.full_path {
+            glob_match(&self.glob, rel.as_os_str().as_bytes())
+        } else {
+            rel.file_name().is_some_and(|name| glob_match(&self.glob, name.as_bytes()))
+        }
+    }
+}
+
+// One line of an ignore file. Unlike `exclude`, a trailing `/` restricts the
+// pattern to directories and a leading `!` re-includes what it matches.
+struct IgnoreRule {
+    pattern: Pattern,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &[u8]) -> Option<Self> {
+        let line = line.trim_ascii_end();
+        if line.is_empty() || line.starts_with(b"#") {
+            return None;
+        }
+        let (negate, line) = match line.strip_prefix(b"!") {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        Some(Self {
+            pattern: Pattern::new(line),
+            negate,
+            dir_only: line.ends_with(b"/"),
+        })
+    }
+}
+
+pub(crate) struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    skip_hidden: bool,
+    // Rules of every ignore file read so far, keyed by the directory holding
+    // it relative to the destination root.
+    ignore: Mutex<HashMap<PathBuf, Vec<IgnoreRule>>>,
+}
+
+impl Filter {
+    pub(crate) fn new(opts: &CopyOptions) -> Self {
+        Self {
+            include: opts.include.iter().map(|p| Pattern::new(p.as_bytes())).collect(),
+            exclude: opts.exclude.iter().map(|p| Pattern::new(p.as_bytes())).collect(),
+            skip_hidden: opts.skip_hidden,
+            ignore: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Reads the ignore file of the source directory `dir`, found at `rel`,
+    // so it applies to everything walked below it.
+    pub(crate) fn load_ignore_file(&self, dir: &Path, rel: &Path) {
+        let rules: Vec<IgnoreRule> = match fs::read(dir.join(IGNORE_FILE)) {
+            Ok(text) => text.split(|&b| b == b'\n').filter_map(IgnoreRule::parse).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Could not read {}: {}", dir.join(IGNORE_FILE).display(), e);
+                return;
+            }
+        };
+        if !rules.is_empty() {
+            self.ignore.lock().unwrap_or_else(|e| e.into_inner()).insert(rel.to_path_buf(), rules);
+        }
+    }
+
+    // Gitignore semantics: files closer to `rel` take precedence, and within
+    // a file the last matching line wins.
+    pub(crate) fn is_ignored(&self, rel: &Path, is_dir: bool) -> bool {
+        let ignore = self.ignore.lock().unwrap_or_else(|e| e.into_inner());
+        if ignore.is_empty() {
+            return false;
+        }
+        let dirs: Vec<&Path> = rel.ancestors().skip(1).collect();
+        let mut ignored = false;
+        for dir in dirs.into_iter().rev() {
+            let (Some(rules), Ok(sub)) = (ignore.get(dir), rel.strip_prefix(dir)) else {
+                continue;
+            };
+            for rule in rules {
+                if (is_dir || !rule.dir_only) && rule.pattern.matches(sub) {
+                    ignored = !rule.negate;
+                }
+            }
+        }
+        ignored
+    }
+
+    pub(crate) fn is_excluded(&self, rel: &Path) -> bool {
+        let hidden = || rel.file_name().is_some_and(|name| name.as_bytes().starts_with(b"."));
+        self.skip_hidden && hidden() || self.exclude.iter().any(|p| p.matches(rel))
+    }
+
+    pub(crate) fn is_included(&self, rel: &Path) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| p.matches(rel))
+    }
+}
+
+fn glob_match(p: &[u8], s: &[u8]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some(b'*') if p.get(1) == Some(&b'*') => {
+            let rest = &p[2..];
+            if let Some(after) = rest.strip_prefix(b"/")
+                && glob_match(after, s)
+            {
+                return true;
+            }
+            (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+        }
+        Some(b'*') => {
+            let rest = &p[1..];
+            for i in 0..=s.len() {
+                if glob_match(rest, &s[i..]) {
+                    return true;
+                }
+                if s.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => matches!(s.first(), Some(&c) if c != b'/') && glob_match(&p[1..], &s[1..]),
+        Some(b'[') => match (s.first(), class_match(&p[1..], s.first().copied())) {
+            (Some(&c), Some((matched, len))) => c != b'/' && matched && glob_match(&p[1 + len..], &s[1..]),
+            (None, Some(_)) => false,
+            (_, None) => s.first() == Some(&b'[') && glob_match(&p[1..], &s[1..]),
+        },
+        Some(b'\\') if p.len() > 1 => s.first() == Some(&p[1]) && glob_match(&p[2..], &s[1..]),
+        Some(&c) => s.first() == Some(&c) && glob_match(&p[1..], &s[1..]),
+    }
+}
+
+fn class_match(p: &[u8], c: Option<u8>) -> Option<(bool, usize)> {
+    let negate = matches!(p.first(), Some(b'!' | b'^'));
+    let mut i = usize::from(negate);
+    let start = i;
+    let mut found = false;
+
+    while i < p.len() {
+        if p[i] == b']' && i > start {
+            return Some((found != negate, i + 1));
+        }
+        let lo = p[i];
+        if i + 2 < p.len() && p[i + 1] == b'-' && p[i + 2] != b']' {
+            found |= c.is_some_and(|c| lo <= c && c <= p[i + 2]);
+            i += 3;
+        } else {
+            found |= c == Some(lo);
+            i += 1;
+        }
+    }
+    None
+}
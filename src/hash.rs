@@ -0,0 +1,345 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::options::HashAlgo;
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// A streaming digest for `VerifyMode::Checksum`, built in or supplied through
+// `HashAlgo::Custom`. Digests are compared as lowercase hex.
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+pub(crate) fn hasher(algo: &HashAlgo) -> Box<dyn Hasher> {
+    match algo {
+        HashAlgo::Crc32 => Box::new(Crc32(!0)),
+        HashAlgo::Sha256 => Box::new(Sha256::new()),
+        #[cfg(feature = "blake3")]
+        HashAlgo::Blake3 => Box::new(Blake3::new()),
+        HashAlgo::Custom(factory) => factory(),
+    }
+}
+
+pub(crate) fn finish(hasher: Box<dyn Hasher>) -> String {
+    hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn hash_file(path: &Path, algo: &HashAlgo, buf: &mut [u8]) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = hasher(algo);
+    loop {
+        match file.read(buf) {
+            Ok(0) => return Ok(finish(hasher)),
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+struct Crc32(u32);
+
+impl Hasher for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.0 = CRC32_TABLE[((self.0 ^ b as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        (!self.0).to_be_bytes().to_vec()
+    }
+}
+
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    filled: usize,
+    len: u64,
+}
+
+impl Hasher for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Sha256::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.finish().to_vec()
+    }
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 { state: SHA256_INIT, block: [0; 64], filled: 0, len: 0 }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.filled).min(data.len());
+            self.block[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+            if self.filled == 64 {
+                self.compress();
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len.wrapping_mul(8);
+        self.block[self.filled] = 0x80;
+        self.block[self.filled + 1..].fill(0);
+        if self.filled >= 56 {
+            self.compress();
+            self.block.fill(0);
+        }
+        self.block[56..].copy_from_slice(&bits.to_be_bytes());
+        self.compress();
+
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+#[cfg(feature = "blake3")]
+const BLAKE3_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+#[cfg(feature = "blake3")]
+const BLAKE3_CHUNK_START: u32 = 1;
+#[cfg(feature = "blake3")]
+const BLAKE3_CHUNK_END: u32 = 2;
+#[cfg(feature = "blake3")]
+const BLAKE3_PARENT: u32 = 4;
+#[cfg(feature = "blake3")]
+const BLAKE3_ROOT: u32 = 8;
+
+// Unkeyed BLAKE3 with a 32-byte output. Input is split into 1 KiB chunks whose
+// chaining values are merged pairwise on `stack` as the tree grows; the IV is
+// the SHA-256 one.
+#[cfg(feature = "blake3")]
+struct Blake3 {
+    stack: Vec<[u32; 8]>,
+    cv: [u32; 8],
+    chunk: u64,
+    block: [u8; 64],
+    filled: usize,
+    blocks: usize,
+}
+
+#[cfg(feature = "blake3")]
+impl Hasher for Blake3 {
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.blocks * 64 + self.filled == 1024 {
+                let mut cv = self.chunk_output().chaining_value();
+                self.chunk += 1;
+                let mut done = self.chunk;
+                while done & 1 == 0 {
+                    cv = parent(self.stack.pop().unwrap_or_default(), cv).chaining_value();
+                    done >>= 1;
+                }
+                self.stack.push(cv);
+                (self.cv, self.blocks, self.filled) = (SHA256_INIT, 0, 0);
+            }
+            if self.filled == 64 {
+                let flags = self.start_flag();
+                self.cv = first_8(compress(&self.cv, &block_words(&self.block), self.chunk, 64, flags));
+                self.blocks += 1;
+                self.filled = 0;
+            }
+            let n = (64 - self.filled).min(data.len());
+            self.block[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+        }
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        let mut output = self.chunk_output();
+        for &left in self.stack.iter().rev() {
+            output = parent(left, output.chaining_value());
+        }
+        let words = compress(&output.cv, &output.block, 0, output.len, output.flags | BLAKE3_ROOT);
+        words[..8].iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl Blake3 {
+    fn new() -> Self {
+        Blake3 { stack: Vec::new(), cv: SHA256_INIT, chunk: 0, block: [0; 64], filled: 0, blocks: 0 }
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks == 0 { BLAKE3_CHUNK_START } else { 0 }
+    }
+
+    fn chunk_output(&self) -> Blake3Output {
+        let mut block = [0u8; 64];
+        block[..self.filled].copy_from_slice(&self.block[..self.filled]);
+        Blake3Output {
+            cv: self.cv,
+            block: block_words(&block),
+            counter: self.chunk,
+            len: self.filled as u32,
+            flags: self.start_flag() | BLAKE3_CHUNK_END,
+        }
+    }
+}
+
+// The last compression of a node, kept apart since the root one is done with
+// `BLAKE3_ROOT` and a zero counter instead.
+#[cfg(feature = "blake3")]
+struct Blake3Output {
+    cv: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    len: u32,
+    flags: u32,
+}
+
+#[cfg(feature = "blake3")]
+impl Blake3Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        first_8(compress(&self.cv, &self.block, self.counter, self.len, self.flags))
+    }
+}
+
+#[cfg(feature = "blake3")]
+fn parent(left: [u32; 8], right: [u32; 8]) -> Blake3Output {
+    let mut block = [0u32; 16];
+    block[..8].copy_from_slice(&left);
+    block[8..].copy_from_slice(&right);
+    Blake3Output { cv: SHA256_INIT, block, counter: 0, len: 64, flags: BLAKE3_PARENT }
+}
+
+#[cfg(feature = "blake3")]
+fn block_words(block: &[u8; 64]) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for (word, chunk) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
+}
+
+#[cfg(feature = "blake3")]
+fn first_8(words: [u32; 16]) -> [u32; 8] {
+    let mut cv = [0u32; 8];
+    cv.copy_from_slice(&words[..8]);
+    cv
+}
+
+#[cfg(feature = "blake3")]
+fn compress(cv: &[u32; 8], block: &[u32; 16], counter: u64, len: u32, flags: u32) -> [u32; 16] {
+    fn g(v: &mut [u32; 16], [a, b, c, d]: [usize; 4], x: u32, y: u32) {
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+        v[d] = (v[d] ^ v[a]).rotate_right(16);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(12);
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+        v[d] = (v[d] ^ v[a]).rotate_right(8);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(7);
+    }
+
+    let mut v = [0u32; 16];
+    v[..8].copy_from_slice(cv);
+    v[8..12].copy_from_slice(&SHA256_INIT[..4]);
+    v[12] = counter as u32;
+    v[13] = (counter >> 32) as u32;
+    v[14] = len;
+    v[15] = flags;
+
+    let mut m = *block;
+    for round in 0..7 {
+        g(&mut v, [0, 4, 8, 12], m[0], m[1]);
+        g(&mut v, [1, 5, 9, 13], m[2], m[3]);
+        g(&mut v, [2, 6, 10, 14], m[4], m[5]);
+        g(&mut v, [3, 7, 11, 15], m[6], m[7]);
+        g(&mut v, [0, 5, 10, 15], m[8], m[9]);
+        g(&mut v, [1, 6, 11, 12], m[10], m[11]);
+        g(&mut v, [2, 7, 8, 13], m[12], m[13]);
+        g(&mut v, [3, 4, 9, 14], m[14], m[15]);
+        if round < 6 {
+            m = BLAKE3_PERMUTATION.map(|i| m[i]);
+        }
+    }
+
+    for i in 0..8 {
+        v[i] ^= v[i + 8];
+        v[i + 8] ^= cv[i];
+    }
+    v
+}
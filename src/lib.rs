@@ -1,157 +1,2200 @@
-use std::collections::HashSet;
-use std::fs;
-use std::io::copy;
-use std::os::unix::fs::{self as unix_fs, FileTypeExt, PermissionsExt};
-use std::path::{Path, PathBuf};
-use walkdir_minimal::WalkDir;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, FileTimes};
+use std::io;
+use std::mem;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ffi::{CStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{self as unix_fs, FileExt, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir_minimal::{WalkDir, WalkError};
 
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "async")]
+mod async_copy;
 pub mod error;
+pub mod estimate;
+mod filter;
+mod hash;
+mod mirror;
 pub mod options;
+pub mod plan;
+mod mover;
+mod pool;
+mod shared;
+pub mod progress;
+pub mod sink;
+pub mod stats;
+mod sys;
+pub mod verify;
+
+use filter::Filter;
+use pool::{FileJob, Pool};
+use shared::{DedupKey, Shared};
+
+#[cfg(feature = "async")]
+pub use async_copy::copy_recursive_async;
+pub use error::{CopyError, CopyErrorKind, CopyFailure};
+pub use estimate::{CopyEstimate, estimate};
+pub use hash::Hasher;
+pub use mover::move_recursive;
+pub use options::{BackupMode, Conflict, ConflictCallback, CopyOptions, CopyOptionsBuilder, DirAction, DirCallback,
+    DestMode, HashAlgo, HasherFactory, OverwriteMode, PathTransform, ReflinkMode, SparseMode, VerifyMode};
+pub use plan::{CopyOp, SkipReason, plan};
+pub use progress::{ProgressCallback, ProgressEvent};
+pub use sink::{CopySink, copy_recursive_to_sink};
+pub use stats::CopyStats;
+pub use verify::{Mismatch, verify_tree};
+
+const MIN_BUFFER_SIZE: usize = 8 * 1024;
+const MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+const SPARSE_BLOCK: usize = 4096;
+#[cfg(not(feature = "no-follow-symlinks"))]
+const MAX_SYMLINK_HOPS: usize = 40;
+const COPY_RANGE_CHUNK: u64 = 8 * 1024 * 1024;
+
+enum Jobs {
+    Inline,
+    Pool(Pool),
+    #[cfg(feature = "async")]
+    Async(tokio::sync::mpsc::Sender<FileJob>),
+}
+
+struct HardLink {
+    src: PathBuf,
+    target: PathBuf,
+    dst: PathBuf,
+}
+
+// A copied directory whose mode, times, and fsync wait until its contents are
+// written, so a read-only source directory does not block its own children.
+struct PendingDir {
+    src: PathBuf,
+    meta: fs::Metadata,
+    path: PathBuf,
+    created: bool,
+}
+
+enum Target {
+    File(PathBuf),
+    Dir(PathBuf),
+    Symlink(PathBuf),
+}
+
+// An entry of the walk. walkdir_minimal keeps its own entry type private, so
+// what the walker yields is taken apart into this one.
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+}
+
+impl Entry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn metadata(&self) -> io::Result<fs::Metadata> {
+        fs::metadata(&self.path)
+    }
+
+    fn symlink_metadata(&self) -> io::Result<fs::Metadata> {
+        fs::symlink_metadata(&self.path)
+    }
+}
+
+struct Context<'a> {
+    opts: &'a CopyOptions,
+    shared: &'a Shared,
+    filter: Arc<Filter>,
+    base_dst: PathBuf,
+    visited: Vec<((u64, u64), PathBuf)>,
+    level: usize,
+    src_root: Option<PathBuf>,
+    dst_root: Option<PathBuf>,
+    prefix: PathBuf,
+    rel_buf: PathBuf,
+    dst_buf: PathBuf,
+    flat_names: HashSet<OsString>,
+    case_folded: HashMap<OsString, (PathBuf, PathBuf)>,
+    root_dev: Option<u64>,
+    dirs: Vec<PendingDir>,
+    inodes: HashMap<(u64, u64), PathBuf>,
+    links: Vec<HardLink>,
+    jobs: Jobs,
+    sink: Option<&'a mut dyn CopySink>,
+    moved: Option<Vec<PathBuf>>,
+    buf: Vec<u8>,
+    stats: CopyStats,
+}
+
+impl<'a> Context<'a> {
+    fn new(opts: &'a CopyOptions, shared: &'a Shared, base_dst: PathBuf, stats: CopyStats) -> Self {
+        Context {
+            opts,
+            shared,
+            filter: Arc::new(Filter::new(opts)),
+            base_dst,
+            visited: Vec::new(),
+            level: 0,
+            src_root: None,
+            dst_root: None,
+            prefix: PathBuf::new(),
+            rel_buf: PathBuf::new(),
+            dst_buf: PathBuf::new(),
+            flat_names: HashSet::new(),
+            case_folded: HashMap::new(),
+            root_dev: None,
+            dirs: Vec::new(),
+            inodes: HashMap::new(),
+            links: Vec::new(),
+            jobs: Jobs::Inline,
+            sink: None,
+            moved: None,
+            buf: Vec::new(),
+            stats,
+        }
+    }
+
+    fn copy_file(&mut self, src: &Path, dst: &Path, meta: &fs::Metadata) -> Result<(), CopyError> {
+        let size = meta.len();
+        if self.opts.max_file_size.is_some_and(|max| size > max)
+            || self.opts.min_file_size.is_some_and(|min| size < min)
+        {
+            warn!("Skipping file by size {} ({} bytes)", src.display(), size);
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Size });
+            self.stats.files_skipped += 1;
+            return Ok(());
+        }
+        if let Some(since) = self.opts.newer_than
+            && meta.modified()? <= since
+        {
+            debug!("Skipping file not modified since the threshold {}", src.display());
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::NotNewer });
+            self.stats.files_skipped += 1;
+            return Ok(());
+        }
+        let dst = &self.place(dst);
+
+        if let Some(sink) = self.sink.as_deref_mut() {
+            if !self.shared.take_file() {
+                self.stats.truncated = true;
+                return Ok(());
+            }
+            let mut input = fs::File::open(src)?;
+            sink.on_file(dst, meta, &mut input)?;
+            self.stats.files_copied += 1;
+            self.stats.bytes_copied += meta.len();
+            return Ok(());
+        }
+
+        if self.opts.preserve_hardlinks && meta.nlink() > 1 {
+            let key = (meta.dev(), meta.ino());
+            if let Some(target) = self.inodes.get(&key) {
+                self.links.push(HardLink { src: src.to_path_buf(), target: target.clone(), dst: dst.to_path_buf() });
+                return Ok(());
+            }
+            self.inodes.insert(key, dst.to_path_buf());
+        }
+
+        let job = FileJob { src: src.to_path_buf(), dst: dst.to_path_buf() };
+        match &self.jobs {
+            Jobs::Inline => copy_one(src, dst, self.opts, self.shared, &mut self.buf, &mut self.stats),
+            Jobs::Pool(pool) => {
+                pool.submit(job);
+                Ok(())
+            }
+            #[cfg(feature = "async")]
+            Jobs::Async(sender) => {
+                // A closed channel means the async side already failed, which
+                // `stopped` picks up on the next entry.
+                let _ = sender.blocking_send(job);
+                Ok(())
+            }
+        }
+    }
+
+    fn copy_special(&mut self, src: &Path, dst: &Path, rel: &Path, meta: &fs::Metadata) -> Result<(), CopyError> {
+        if !self.filter.is_included(rel) {
+            return Ok(());
+        }
+        if !self.opts.copy_special || self.sink.is_some() || meta.file_type().is_socket() {
+            if self.opts.strict {
+                return Err(CopyError::NotSupported(src.to_path_buf()));
+            }
+            debug!("Skipping special file {}", src.display());
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Special });
+            self.stats.skipped_special.push(src.to_path_buf());
+            return Ok(());
+        }
+        let dst = &self.place(dst);
+        create_special(src, meta, dst, self.opts, &mut self.stats)?;
+        self.track(src);
+        Ok(())
+    }
+
+    fn copy_symlink(&mut self, src: &Path, dst: &Path, rel: &Path, meta: &fs::Metadata) -> Result<(), CopyError> {
+        if !self.filter.is_included(rel) {
+            return Ok(());
+        }
+        let dst = &self.place(dst);
+        if let Some(sink) = self.sink.as_deref_mut() {
+            sink.on_symlink(dst, &fs::read_link(src)?, meta)?;
+            self.stats.symlinks_created += 1;
+        } else {
+            let roots = match (&self.src_root, &self.dst_root) {
+                (Some(src_root), Some(dst_root)) if self.opts.rewrite_symlink_targets => {
+                    Some((src_root.as_path(), dst_root.as_path()))
+                }
+                _ => None,
+            };
+            recreate_symlink(src, dst, roots, self.opts, &mut self.stats)?;
+            self.track(src);
+        }
+        Ok(())
+    }
+
+    // With `flatten`, moves `dst` straight into the destination root under
+    // the first of `name`, `name (1)`, ... not yet handed out by this copy.
+    fn place(&mut self, dst: &Path) -> PathBuf {
+        let (true, Some(root), Some(name)) = (self.opts.flatten, &self.dst_root, dst.file_name()) else {
+            return dst.to_path_buf();
+        };
+        let mut unique = name.to_os_string();
+        let path = Path::new(name);
+        for n in 1.. {
+            if self.flat_names.insert(unique.clone()) {
+                break;
+            }
+            unique = path.file_stem().unwrap_or(name).to_os_string();
+            unique.push(format!(" ({n})"));
+            if let Some(ext) = path.extension() {
+                unique.push(".");
+                unique.push(ext);
+            }
+        }
+        root.join(unique)
+    }
+
+    // With `detect_case_collisions`, refuses `src` when its destination only
+    // differs in case from that of an entry copied before.
+    fn check_case(&mut self, src: &Path, dst: &Path) -> Result<(), CopyError> {
+        if !self.opts.detect_case_collisions {
+            return Ok(());
+        }
+        let folded = match dst.to_str() {
+            Some(s) => OsString::from(s.to_lowercase()),
+            None => OsString::from_vec(dst.as_os_str().as_bytes().to_ascii_lowercase()),
+        };
+        if let Some((existing, seen)) = self.case_folded.get(&folded) {
+            if seen != dst {
+                return Err(CopyError::CaseCollision { existing: existing.clone(), new: src.to_path_buf() });
+            }
+            return Ok(());
+        }
+        self.case_folded.insert(folded, (src.to_path_buf(), dst.to_path_buf()));
+        Ok(())
+    }
+
+    fn record(&mut self, path: &Path, res: Result<(), CopyError>) -> Result<(), CopyError> {
+        match res {
+            Err(e) if self.opts.continue_on_error && !e.is_fatal() => {
+                if let Some(e) = plan::record_error(e) {
+                    self.stats.errors.push((path.to_path_buf(), e));
+                }
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    fn track(&mut self, src: &Path) {
+        // Entries below a followed symlink belong to the link target, which a
+        // move must leave alone; only the link itself is removed.
+        if self.level == 0
+            && let Some(moved) = &mut self.moved
+        {
+            moved.push(src.to_path_buf());
+        }
+    }
+
+    // Directories at the `depth` limit are created but not entered; unless
+    // they are known to be empty, the copy is incomplete.
+    fn depth_reached(&mut self, dir: &Path) -> Result<(), CopyError> {
+        if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none()) {
+            return Ok(());
+        }
+        if self.opts.fail_on_depth {
+            return Err(CopyError::DepthExceeded(dir.to_path_buf()));
+        }
+        self.stats.truncated_dirs.push(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn stopped(&self) -> bool {
+        match &self.jobs {
+            Jobs::Inline => false,
+            Jobs::Pool(pool) => pool.failed(),
+            #[cfg(feature = "async")]
+            Jobs::Async(sender) => sender.is_closed(),
+        }
+    }
+}
+
+pub fn copy_recursive(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<CopyStats, CopyFailure> {
+    let (stats, _) = copy_tree(src, dst, opts, false)?;
+    log_finished(src, dst, &stats);
+    Ok(stats)
+}
+
+// Like `cp a b c dst/`: each source lands in the directory `dst`, which is
+// created if missing. One call shares the throttle, the size budget, and the
+// hard links seen, so these span all sources.
+pub fn copy_many(srcs: &[PathBuf], dst: &Path, opts: &CopyOptions) -> Result<CopyStats, CopyFailure> {
+    let failed = |error: CopyError| CopyFailure { stats: Box::default(), error };
+    check_dst_symlink(dst, opts).map_err(failed)?;
+    if dst.exists() && !dst.is_dir() {
+        return Err(failed(CopyError::DestNotDir(dst.to_path_buf())));
+    }
+    if !dst.exists() && !opts.dry_run {
+        check_parent(dst, opts).map_err(failed)?;
+        fs::create_dir_all(dst).map_err(|e| failed(e.into()))?;
+    }
+
+    let shared = Shared::new(opts);
+    let mut inodes = HashMap::new();
+    let mut stats = CopyStats::default();
+    // Each source is mapped to its path inside `dst` here, since a dry run
+    // leaves `dst` missing.
+    let placed = CopyOptions { dest_mode: DestMode::As, ..opts.clone() };
+    for src in srcs {
+        if let Err(error) = opts.check_cancelled() {
+            return Err(CopyFailure { stats: Box::new(stats), error });
+        }
+        if shared.files_exhausted() {
+            stats.truncated = true;
+            break;
+        }
+        let target = if opts.copies_contents(src) {
+            dst.to_path_buf()
+        } else {
+            dst.join(src.file_name().unwrap_or_default())
+        };
+        match copy_tree_with(src, &target, &placed, &shared, &mut inodes, false) {
+            Ok((copied, _)) => {
+                log_finished(src, dst, &copied);
+                stats.merge(copied);
+            }
+            Err(failure) if opts.continue_on_error && !failure.error.is_fatal() => {
+                stats.merge(*failure.stats);
+                if let Some(e) = plan::record_error(failure.error) {
+                    stats.errors.push((src.clone(), e));
+                }
+            }
+            Err(failure) => {
+                stats.merge(*failure.stats);
+                stats.destination = dst.to_path_buf();
+                return Err(CopyFailure { stats: Box::new(stats), error: failure.error });
+            }
+        }
+    }
+    stats.destination = dst.to_path_buf();
+    Ok(stats)
+}
+
+// Streams the file `src` to `out` (stdout, a pipe, a compressor, ...) through
+// the same buffered loop as a copy, and returns the number of bytes written.
+pub fn copy_file_stream(src: &Path, out: &mut impl Write, opts: &CopyOptions) -> Result<u64, CopyError> {
+    // Builds with `no-follow-symlinks` refuse a link rather than read through it.
+    if cfg!(feature = "no-follow-symlinks") && src.is_symlink() {
+        return Err(CopyError::NotSupported(src.to_path_buf()));
+    }
+    let mut input = match retry(opts, || fs::File::open(src)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(CopyError::SrcNotFound(src.to_path_buf())),
+        res => res?,
+    };
+    let meta = input.metadata()?;
+    if !meta.is_file() {
+        return Err(CopyError::NotSupported(src.to_path_buf()));
+    }
+    let total = meta.len();
+    let shared = Shared::new(opts);
+    shared.charge(src, total)?;
+
+    if let Some(progress) = &opts.progress {
+        progress(&ProgressEvent::FileStarted { path: src, size: total });
+    }
+    let mut buf = vec![0; buffer_len(opts)];
+    let mut so_far = 0;
+    loop {
+        opts.check_cancelled()?;
+        let n = match retry(opts, || input.read(&mut buf))? {
+            0 => break,
+            n => n,
+        };
+        out.write_all(&buf[..n])?;
+        shared.throttle(n as u64);
+        so_far += n as u64;
+        if let Some(progress) = &opts.progress {
+            progress(&ProgressEvent::BytesCopied { path: src, so_far, total });
+        }
+    }
+    out.flush()?;
+    if let Some(progress) = &opts.progress {
+        progress(&ProgressEvent::FileFinished { path: src });
+    }
+    Ok(so_far)
+}
+
+// `dst` may not exist yet, so the device that counts is the one of its nearest
+// existing ancestor, where the copy would be created.
+pub fn same_filesystem(src: &Path, dst: &Path) -> io::Result<bool> {
+    let src_dev = fs::metadata(src)?.dev();
+    let mut last_err = None;
+    for dir in dst.ancestors() {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        match fs::metadata(dir) {
+            Ok(meta) => return Ok(meta.dev() == src_dev),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => last_err = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotFound)))
+}
+
+// The birth time comes from `statx`; filesystems and kernels that do not
+// record it give `None` rather than an error.
+pub fn creation_time(path: &Path) -> io::Result<Option<SystemTime>> {
+    match fs::metadata(path)?.created() {
+        Ok(time) => Ok(Some(time)),
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn log_finished(src: &Path, dst: &Path, stats: &CopyStats) {
+    debug!("Copied {} to {}: {} files, {} bytes, {} skipped, {} errors",
+        src.display(), dst.display(), stats.files_copied, stats.bytes_copied, stats.files_skipped, stats.errors.len()
+    );
+}
+
+// With `track`, also returns the source entries that were copied, parents
+// before children, so a move can remove them afterwards.
+fn copy_tree(src: &Path, dst: &Path, opts: &CopyOptions, track: bool
+) -> Result<(CopyStats, Vec<PathBuf>), CopyFailure> {
+    copy_tree_with(src, dst, opts, &Shared::new(opts), &mut HashMap::new(), track)
+}
+
+// `inodes` maps the source files seen with `preserve_hardlinks` to their
+// first destination, and carries over from one call to the next.
+fn copy_tree_with(src: &Path, dst: &Path, opts: &CopyOptions, shared: &Shared,
+    inodes: &mut HashMap<(u64, u64), PathBuf>, track: bool
+) -> Result<(CopyStats, Vec<PathBuf>), CopyFailure> {
+    let mut stats = CopyStats::default();
+    match copy_target(src, dst, opts, shared, inodes, track, &mut stats) {
+        Ok(copied) => Ok((stats, copied)),
+        Err(error) => Err(CopyFailure { stats: Box::new(stats), error }),
+    }
+}
+
+// `stats` is filled in as the copy goes, so it holds what was done even when
+// an error ends it.
+fn copy_target(src: &Path, dst: &Path, opts: &CopyOptions, shared: &Shared,
+    inodes: &mut HashMap<(u64, u64), PathBuf>, track: bool, stats: &mut CopyStats
+) -> Result<Vec<PathBuf>, CopyError> {
+    match resolve_target(src, dst, opts)? {
+        Target::File(dest_path) => {
+            stats.destination = dest_path.clone();
+            let meta = fs::metadata(src)?;
+            if opts.preserve_hardlinks && meta.nlink() > 1 {
+                let key = (meta.dev(), meta.ino());
+                if let Some(target) = inodes.get(&key) {
+                    let link = HardLink { src: src.to_path_buf(), target: target.clone(), dst: dest_path.clone() };
+                    make_link(&link, opts, stats)?;
+                    sync_parent(opts, &dest_path)?;
+                    return Ok(vec![src.to_path_buf()]);
+                }
+                inodes.insert(key, dest_path.clone());
+            }
+            copy_one(src, &dest_path, opts, shared, &mut Vec::new(), stats)?;
+            sync_parent(opts, &dest_path)?;
+            Ok(vec![src.to_path_buf()])
+        }
+        Target::Symlink(dest_path) => {
+            stats.destination = dest_path.clone();
+            recreate_symlink(src, &dest_path, None, opts, stats)?;
+            sync_parent(opts, &dest_path)?;
+            Ok(vec![src.to_path_buf()])
+        }
+        Target::Dir(base_dst) => {
+            let mut ctx = Context::new(opts, shared, base_dst.clone(), mem::take(stats));
+            ctx.inodes = mem::take(inodes);
+            if track {
+                ctx.moved = Some(vec![src.to_path_buf()]);
+            }
+            let copied = copy_dir(src, &base_dst, &mut ctx);
+            *inodes = mem::take(&mut ctx.inodes);
+            ctx.stats.destination = base_dst;
+            *stats = ctx.stats;
+            copied?;
+            Ok(ctx.moved.unwrap_or_default())
+        }
+    }
+}
+
+fn copy_dir(src: &Path, base_dst: &Path, ctx: &mut Context) -> Result<(), CopyError> {
+    let (opts, shared) = (ctx.opts, ctx.shared);
+    let threads = opts.worker_threads();
+    if threads > 1 {
+        let (walked, worker_stats) = pool::with_pool(threads, opts, shared, |pool| {
+            ctx.jobs = Jobs::Pool(pool);
+            let walked = walk_and_copy(src, base_dst, ctx);
+            ctx.jobs = Jobs::Inline;
+            walked
+        });
+        ctx.stats.merge(worker_stats);
+        walked?;
+    } else {
+        walk_and_copy(src, base_dst, ctx)?;
+    }
+    let links = mem::take(&mut ctx.links);
+    finish_tree(src, base_dst, opts, &ctx.filter, links, &mut ctx.dirs, &mut ctx.stats)
+}
+
+// What follows the walk of a directory once every file job is done, for the
+// sync and the async copy alike.
+fn finish_tree(src: &Path, base_dst: &Path, opts: &CopyOptions, filter: &Arc<Filter>, links: Vec<HardLink>,
+    dirs: &mut Vec<PendingDir>, stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    link_files(opts, links, stats)?;
+    if opts.mirrors() {
+        mirror::delete_extraneous(src, base_dst, filter, opts, stats)?;
+    }
+    if opts.prune_empty_dirs && !opts.dry_run {
+        prune_empty_dirs(base_dst, dirs, stats)?;
+    }
+    finish_dirs(opts, dirs)?;
+    sync_parent(opts, base_dst)
+}
+
+fn resolve_target(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<Target, CopyError> {
+    check_dst_symlink(dst, opts)?;
+    if opts.dest_mode == DestMode::Into && dst.exists() && !dst.is_dir() {
+        return Err(CopyError::DestNotDir(dst.to_path_buf()));
+    }
+    // Files and links only; they never replace a directory.
+    let dest_path = || {
+        if opts.lands_inside(src, dst) {
+            Ok(dst.join(src.file_name().unwrap_or_default()))
+        } else if opts.dest_mode == DestMode::As && dst.is_dir() {
+            Err(CopyError::Io(io::Error::new(io::ErrorKind::IsADirectory,
+                format!("destination is a directory: {}", dst.display())
+            )))
+        } else {
+            Ok(dst.to_path_buf())
+        }
+    };
+
+    // Checked first so that a dangling link can still be copied as a link.
+    if !opts.follows_top_level_symlink() && src.is_symlink() {
+        let dest_path = dest_path()?;
+        check_same_file(src, &dest_path, false)?;
+        check_parent(&dest_path, opts)?;
+        return Ok(Target::Symlink(dest_path));
+    }
+
+    if !src.exists() {
+        return Err(CopyError::SrcNotFound(src.to_path_buf()));
+    }
+
+    if src.is_file() {
+        if opts.content_only {
+            debug!("content_only has no effect on the file {}", src.display());
+        }
+        // Whether a destination that is the source itself is an error is up
+        // to `copy_one`, once it knows if the file would be written.
+        let dest_path = dest_path()?;
+        check_parent(&dest_path, opts)?;
+        return Ok(Target::File(dest_path));
+    }
+
+    if src.is_dir() {
+        let conflict = dst.exists() && !dst.is_dir();
+        if conflict && !opts.replace_dest {
+            return Err(CopyError::DestNotDir(dst.to_path_buf()));
+        }
+
+        let base_dst = if opts.lands_inside(src, dst) {
+            dst.join(src.file_name().unwrap_or_default())
+        } else {
+            dst.to_path_buf()
+        };
+
+        check_same_file(src, &base_dst, true)?;
+        if resolve_path(&base_dst)?.starts_with(src.canonicalize()?) {
+            return Err(CopyError::DestInsideSrc(base_dst));
+        }
+        check_parent(&base_dst, opts)?;
+        if conflict {
+            clear_conflict(dst, true, opts)?;
+        }
+
+        return Ok(Target::Dir(base_dst));
+    }
+
+    Err(CopyError::NotSupported(src.to_path_buf()))
+}
+
+// Refuses to copy onto the source itself, whatever path leads there. Links
+// are compared themselves unless `follow` is set.
+fn check_same_file(src: &Path, dst: &Path, follow: bool) -> Result<(), CopyError> {
+    let stat = if follow { fs::metadata } else { fs::symlink_metadata };
+    if let (Ok(src_meta), Ok(dst_meta)) = (stat(src), stat(dst))
+        && same_inode(&src_meta, &dst_meta)
+    {
+        return Err(CopyError::SameFile(dst.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn same_inode(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+// Everything below follows a symlinked `dst`, so it is refused up front
+// when that is unwanted.
+fn check_dst_symlink(dst: &Path, opts: &CopyOptions) -> Result<(), CopyError> {
+    if !opts.follow_dst_symlink && dst.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(CopyError::DestIsSymlink(dst.to_path_buf()));
+    }
+    Ok(())
+}
+
+// With `replace_dest`, moves an entry of the wrong kind out of the way of
+// `dst`, into its backup if one is configured: anything but a directory where
+// one is wanted, or a directory where it is not. Returns whether it did.
+fn clear_conflict(dst: &Path, want_dir: bool, opts: &CopyOptions) -> Result<bool, CopyError> {
+    if !opts.replace_dest {
+        return Ok(false);
+    }
+    let Ok(meta) = fs::symlink_metadata(dst) else {
+        return Ok(false);
+    };
+    let conflict = if want_dir { !dst.is_dir() } else { meta.is_dir() };
+    if !conflict {
+        return Ok(false);
+    }
+    plan::record(|| CopyOp::Delete { dst: dst.to_path_buf() });
+    if !opts.dry_run {
+        match backup_path(dst, opts.backup)? {
+            Some(backup) => fs::rename(dst, backup)?,
+            None if meta.is_dir() => fs::remove_dir_all(dst)?,
+            None => fs::remove_file(dst)?,
+        }
+    }
+    Ok(true)
+}
+
+// Only the top-level destination is checked; everything below it is created
+// by the copy itself.
+fn check_parent(dest: &Path, opts: &CopyOptions) -> Result<(), CopyError> {
+    if !opts.create_parents
+        && let Some(parent) = dest.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.is_dir()
+    {
+        return Err(CopyError::DestParentMissing(parent.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn link_files(opts: &CopyOptions, links: Vec<HardLink>, stats: &mut CopyStats) -> Result<(), CopyError> {
+    for link in links {
+        match make_link(&link, opts, stats) {
+            Err(e) if opts.continue_on_error && !e.is_fatal() => stats.errors.push((link.src, e)),
+            res => res?,
+        }
+    }
+    Ok(())
+}
+
+fn make_link(link: &HardLink, opts: &CopyOptions, stats: &mut CopyStats) -> Result<(), CopyError> {
+    if let Ok(dst_meta) = fs::symlink_metadata(&link.dst) {
+        if !should_replace(opts, &fs::metadata(&link.src)?, &dst_meta) {
+            plan::record(|| CopyOp::Skip { src: link.src.clone(), dst: link.dst.clone(), reason: SkipReason::Exists });
+            stats.files_skipped += 1;
+            return Ok(());
+        }
+        if !opts.dry_run {
+            fs::remove_file(&link.dst)?;
+        }
+    }
+
+    plan::record(|| CopyOp::HardLink { src: link.src.clone(), dst: link.dst.clone() });
+    if !opts.dry_run {
+        if let Some(p) = link.dst.parent() {
+            fs::create_dir_all(p)?;
+        }
+        fs::hard_link(&link.target, &link.dst)?;
+    }
+    stats.hardlinks_created += 1;
+    Ok(())
+}
+
+// Removes the directories this copy created that ended up empty. Children
+// come after their parents in `dirs`, so walking it backwards also catches
+// parents that only held pruned directories.
+fn prune_empty_dirs(base_dst: &Path, dirs: &mut Vec<PendingDir>, stats: &mut CopyStats) -> Result<(), CopyError> {
+    let mut kept = Vec::with_capacity(dirs.len());
+    for dir in mem::take(dirs).into_iter().rev() {
+        if dir.created && dir.path != base_dst {
+            match fs::remove_dir(&dir.path) {
+                Ok(()) => {
+                    stats.dirs_created -= 1;
+                    continue;
+                }
+                Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        kept.push(dir);
+    }
+    kept.reverse();
+    *dirs = kept;
+    Ok(())
+}
+
+fn finish_dirs(opts: &CopyOptions, dirs: &[PendingDir]) -> Result<(), CopyError> {
+    if opts.dry_run {
+        return Ok(());
+    }
+    for dir in dirs.iter().rev() {
+        // Directories that already existed keep their mode, as with `cp -r`.
+        if dir.created {
+            fs::set_permissions(&dir.path, fs::Permissions::from_mode(dir.meta.mode() & mode_mask(opts)))?;
+        }
+        if opts.preserve_timestamps {
+            copy_times(&dir.meta, &dir.path)?;
+        }
+        if opts.fsync {
+            sys::sync_dir(&dir.path)?;
+        }
+        if opts.preserve_flags {
+            copy_flags(&dir.src, &dir.path)?;
+        }
+    }
+    Ok(())
+}
+
+// Applied last: an immutable or append-only file can no longer be renamed or
+// have its times changed, nor, once immutable, gain directory entries. Those
+// two need `CAP_LINUX_IMMUTABLE`; without it, or on filesystems without inode
+// flags, the flags are left off.
+fn copy_flags(src: &Path, dst: &Path) -> Result<(), CopyError> {
+    let res = sys::inode_flags(src).and_then(|flags| {
+        let old = sys::inode_flags(dst)?;
+        let new = old & !sys::COPIED_FLAGS | flags & sys::COPIED_FLAGS;
+        if new != old {
+            sys::set_inode_flags(dst, new)?;
+        }
+        Ok(())
+    });
+    match res {
+        Err(e) if sys::is_unsupported(&e) || e.kind() == io::ErrorKind::PermissionDenied => {
+            debug!("Not copying inode flags of {}: {}", src.display(), e);
+            Ok(())
+        }
+        res => Ok(res?),
+    }
+}
+
+// Lifts the immutable and append-only flags off a destination about to be
+// replaced or changed. If that fails, so will the change, with a clear error.
+fn unlock_flags(dst: &Path) {
+    let locked = sys::FS_IMMUTABLE_FL | sys::FS_APPEND_FL;
+    if let Ok(flags) = sys::inode_flags(dst)
+        && flags & locked != 0
+    {
+        let _ = sys::set_inode_flags(dst, flags & !locked);
+    }
+}
+
+fn sync_parent(opts: &CopyOptions, path: &Path) -> Result<(), CopyError> {
+    if opts.fsync && !opts.dry_run {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => sys::sync_dir(parent)?,
+            _ => sys::sync_dir(Path::new("."))?,
+        }
+    }
+    Ok(())
+}
+
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    let abs = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut existing = abs.as_path();
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(real) => {
+                return Ok(rest.iter().rev().fold(real, |acc: PathBuf, c| acc.join(c)));
+            }
+            Err(e) => match (existing.parent(), existing.components().next_back()) {
+                (Some(parent), Some(last)) => {
+                    rest.push(last.as_os_str().to_os_string());
+                    existing = parent;
+                }
+                _ => return Err(e),
+            },
+        }
+    }
+}
+
+fn walk_and_copy(src: &Path, dst: &Path, ctx: &mut Context) -> Result<(), CopyError> {
+    walk_dir(src, src, dst, ctx)
+}
+
+// Walks the directory `src`, reached through `via`: the source itself or a
+// followed symlink.
+fn walk_dir(src: &Path, via: &Path, dst: &Path, ctx: &mut Context) -> Result<(), CopyError> {
+    let real_src = || src.canonicalize().unwrap_or_else(|_| src.to_path_buf());
+    ctx.src_root.get_or_insert_with(real_src);
+    if ctx.dst_root.is_none() {
+        ctx.prefix = dst.strip_prefix(&ctx.base_dst).unwrap_or(Path::new("")).to_path_buf();
+        ctx.dst_root = Some(dst.to_path_buf());
+    }
+
+    // Followed links reach the same directory under many spellings
+    // (`a/link/..`, absolute targets), so loops are detected on the inode.
+    let meta = fs::metadata(src)?;
+    let key = (meta.dev(), meta.ino());
+    if let Some(start) = ctx.visited.iter().position(|(visited, _)| *visited == key) {
+        let chain = ctx.visited[start..].iter().map(|(_, path)| path.clone()).collect();
+        return Err(CopyError::SymlinkLoop { at: via.to_path_buf(), chain });
+    }
+
+    ctx.visited.push((key, via.to_path_buf()));
+    let walked = walk_tree(src, dst, meta, ctx);
+    ctx.visited.pop();
+    walked
+}
+
+// Follows a chain of symlinks to the first non-link, giving up after as
+// many hops as the kernel allows before `ELOOP`.
+#[cfg(not(feature = "no-follow-symlinks"))]
+fn resolve_symlink(link: &Path) -> Result<(PathBuf, fs::Metadata), CopyError> {
+    let mut chain = vec![link.to_path_buf()];
+    let mut path = link.to_path_buf();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let target = fs::read_link(&path)?;
+        path = path.parent().unwrap_or_else(|| Path::new("/")).join(target);
+        let meta = match path.symlink_metadata() {
+            Err(e) if e.raw_os_error() == Some(sys::ELOOP) => {
+                return Err(CopyError::SymlinkLoop { at: link.to_path_buf(), chain });
+            }
+            res => res?,
+        };
+        if !meta.file_type().is_symlink() {
+            return Ok((path, meta));
+        }
+        // Back at a link already followed, under the same spelling.
+        if chain.contains(&path) {
+            break;
+        }
+        chain.push(path.clone());
+    }
+    Err(CopyError::SymlinkLoop { at: link.to_path_buf(), chain })
+}
+
+// Where the link points, with `.` and `..` folded away without touching the
+// filesystem, so dangling targets can be checked too.
+#[cfg(not(feature = "no-follow-symlinks"))]
+fn lexical_target(link: &Path) -> Result<PathBuf, CopyError> {
+    let parent = link.parent().unwrap_or_else(|| Path::new("/")).canonicalize()?;
+    let mut target = PathBuf::new();
+    for component in parent.join(fs::read_link(link)?).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                target.pop();
+            }
+            c => target.push(c),
+        }
+    }
+    Ok(target)
+}
+
+fn walk_tree(src: &Path, dst: &Path, root_meta: fs::Metadata, ctx: &mut Context) -> Result<(), CopyError> {
+    let opts = ctx.opts;
+    let prefix = ctx.prefix.clone();
+    let walk_root = src.to_path_buf();
+    let filter = Arc::clone(&ctx.filter);
+
+    let root_dev = *ctx.root_dev.get_or_insert(root_meta.dev());
+    if opts.one_file_system && root_meta.dev() != root_dev {
+        return Ok(());
+    }
+
+    // Flattened directories below the root only exist to be walked.
+    if !opts.flatten || ctx.dst_root.as_deref() == Some(dst) {
+        if let Some(sink) = ctx.sink.as_deref_mut() {
+            if !dst.as_os_str().is_empty() {
+                sink.on_dir(dst, &root_meta)?;
+                ctx.stats.dirs_created += 1;
+            }
+        } else {
+            let created = !dst.exists();
+            if created {
+                if !opts.dry_run {
+                    fs::create_dir_all(dst)?;
+                }
+                plan::record(|| CopyOp::CreateDir { src: src.to_path_buf(), dst: dst.to_path_buf() });
+                ctx.stats.dirs_created += 1;
+            }
+            if !opts.dry_run {
+                if opts.preserve_flags && !created {
+                    unlock_flags(dst);
+                }
+                if opts.preserve_owner {
+                    let (uid, gid) = owner(src, &root_meta, opts)?;
+                    copy_owner(unix_fs::chown(dst, uid, gid), &mut ctx.stats)?;
+                }
+                if opts.preserve_xattr {
+                    copy_xattrs(src, dst)?;
+                }
+                if opts.preserve_acl {
+                    copy_acls(src, dst, true)?;
+                }
+                if created || opts.preserve_timestamps || opts.fsync || opts.preserve_flags {
+                    ctx.dirs.push(PendingDir { src: src.to_path_buf(), meta: root_meta, path: dst.to_path_buf(), created });
+                }
+            }
+        }
+    }
+
+    // `depth` counts levels below the top-level source, which walkdir yields
+    // one level past its `max_depth`.
+    let Some(max_depth) = opts.depth.checked_sub(ctx.level + 1) else {
+        return ctx.depth_reached(src);
+    };
+
+    // Directories on another filesystem are pruned by the walker and handed
+    // back here so the mount point itself is still created, like `cp -x`.
+    // Directories `on_dir` skips take the same route.
+    let mount_points = Rc::new(RefCell::new(Vec::new()));
+    let pruned = Rc::clone(&mount_points);
+    let one_file_system = opts.one_file_system;
+    let respect_ignore_files = opts.respect_ignore_files;
+    let transform = opts.path_transform.clone();
+    let on_dir = opts.on_dir.clone();
+    let (walk_dst, base_dst) = (dst.to_path_buf(), ctx.base_dst.clone());
+    let renamed = Rc::new(RefCell::new(HashMap::new()));
+    let mapped = Rc::clone(&renamed);
+    if respect_ignore_files {
+        filter.load_ignore_file(src, &prefix);
+    }
+
+    let rel_buf = RefCell::new(PathBuf::new());
+    let walker = WalkDir::new(src)?.max_depth(max_depth).filter_entry(move |e| {
+        let rel_part = e.path().strip_prefix(&walk_root).unwrap_or(e.path());
+        let mut rel = rel_buf.borrow_mut();
+        set_path(&mut rel, &prefix, rel_part);
+        if filter.is_excluded(&rel) {
+            return false;
+        }
+        let is_dir = (respect_ignore_files || on_dir.is_some()) && e.file_type().is_ok_and(|t| t.is_dir());
+        if respect_ignore_files {
+            if filter.is_ignored(&rel, is_dir) {
+                return false;
+            }
+            if is_dir {
+                filter.load_ignore_file(e.path(), &rel);
+            }
+        }
+        let new_rel = match &transform {
+            Some(transform) => match transform(&rel) {
+                Some(new_rel) => Some(new_rel),
+                None => return false,
+            },
+            None => None,
+        };
+        let mut skip = false;
+        if is_dir && let Some(on_dir) = &on_dir {
+            let target = match &new_rel {
+                Some(new_rel) => base_dst.join(new_rel),
+                None => walk_dst.join(rel_part),
+            };
+            match on_dir(e.path(), &target) {
+                DirAction::Descend => {}
+                DirAction::Skip => skip = true,
+                DirAction::SkipRecursive => return false,
+            }
+        }
+        if let Some(new_rel) = new_rel {
+            mapped.borrow_mut().insert(e.path().to_path_buf(), new_rel);
+        }
+        let mount_point = || e.symlink_metadata().is_ok_and(|meta| meta.is_dir() && meta.dev() != root_dev);
+        if skip || one_file_system && mount_point() {
+            pruned.borrow_mut().push(Entry { path: e.path().to_path_buf(), depth: e.depth() });
+            return false;
+        }
+        true
+    });
+    // Sorting needs the whole walk up front; the walker then has nothing
+    // left to yield.
+    let mut walker = walker.map(|item| item.map(|e| Entry { path: e.path().to_path_buf(), depth: e.depth() }));
+    let mut sorted = None;
+    if opts.sort_by_name {
+        let mut items = Vec::new();
+        for item in walker.by_ref() {
+            opts.check_cancelled()?;
+            items.extend(mount_points.borrow_mut().drain(..).map(Ok));
+            items.push(item);
+        }
+        items.extend(mount_points.borrow_mut().drain(..).map(Ok));
+        items.sort_by(|a, b| match (a, b) {
+            (Ok(a), Ok(b)) => a.path().cmp(b.path()),
+            (a, b) => a.is_ok().cmp(&b.is_ok()),
+        });
+        sorted = Some(items.into_iter());
+    }
+    loop {
+        let next = match &mut sorted {
+            Some(items) => items.next(),
+            None => walker.next(),
+        };
+        let mounts: Vec<Entry> = mount_points.borrow_mut().drain(..).collect();
+        for mount in mounts {
+            let new_rel = renamed.borrow_mut().remove(mount.path());
+            let copied = copy_entry(src, dst, &mount, new_rel, ctx);
+            ctx.record(mount.path(), copied)?;
+        }
+
+        let Some(entry_res) = next else {
+            break;
+        };
+        if ctx.stopped() {
+            return Ok(());
+        }
+        // Once `max_files` is used up the rest of the tree is left alone
+        // rather than walked for nothing.
+        if ctx.shared.files_exhausted() {
+            ctx.stats.truncated = true;
+            return Ok(());
+        }
+        opts.check_cancelled()?;
+        match entry_res {
+            Ok(entry) => {
+                let new_rel = renamed.borrow_mut().remove(entry.path());
+                let copied = copy_entry(src, dst, &entry, new_rel, ctx);
+                ctx.record(entry.path(), copied)?;
+            }
+            Err(WalkError::Io(e, _)) if skips_unreadable(opts, &e) => {
+                warn!("Skipping unreadable entry under {}: {}", src.display(), e);
+                ctx.stats.files_skipped += 1;
+            }
+            Err(e) => ctx.record(src, Err(e.into()))?,
+        }
+    }
+
+    Ok(())
+}
+
+// `new_rel` is where `path_transform` moved the entry, relative to the
+// destination root; filters keep matching the original relative path.
+fn copy_entry(src: &Path, dst: &Path, entry: &Entry, new_rel: Option<PathBuf>, ctx: &mut Context
+) -> Result<(), CopyError> {
+    // Both paths are built in buffers kept on the context, which saves two
+    // allocations per entry on trees of many small files.
+    let rel_part = entry.path().strip_prefix(src).unwrap_or(entry.path());
+    let mut rel = mem::take(&mut ctx.rel_buf);
+    set_path(&mut rel, &ctx.prefix, rel_part);
+    let mut dst_path = mem::take(&mut ctx.dst_buf);
+    let copied = match new_rel {
+        Some(new_rel) => transformed_path(&ctx.base_dst, &new_rel, !ctx.opts.dry_run && ctx.sink.is_none())
+            .and_then(|path| copy_entry_to(src, entry, &rel, &path, ctx)),
+        None => {
+            set_path(&mut dst_path, dst, rel_part);
+            copy_entry_to(src, entry, &rel, &dst_path, ctx)
+        }
+    };
+    ctx.rel_buf = rel;
+    ctx.dst_buf = dst_path;
+    copied
+}
+
+fn set_path(buf: &mut PathBuf, base: &Path, rel: &Path) {
+    buf.as_mut_os_string().clear();
+    buf.push(base);
+    buf.push(rel);
+}
+
+#[cfg_attr(feature = "no-follow-symlinks", allow(unused_variables))]
+fn copy_entry_to(src: &Path, entry: &Entry, rel_path: &Path, dst_path: &Path, ctx: &mut Context
+) -> Result<(), CopyError> {
+    let opts = ctx.opts;
+    let src_path = entry.path();
+    let meta = entry.symlink_metadata()?;
+    let ft = meta.file_type();
+
+    if opts.min_depth > ctx.level + entry.depth() {
+        let followed_dir = ft.is_symlink() && opts.follows_symlinks() && entry.metadata().is_ok_and(|m| m.is_dir());
+        if !ft.is_dir() && !followed_dir {
+            return Ok(());
+        }
+    }
+    if !ft.is_file() || ctx.filter.is_included(rel_path) {
+        ctx.check_case(src_path, dst_path)?;
+    }
+
+    if ft.is_block_device() || ft.is_char_device() || ft.is_fifo() || ft.is_socket() {
+        return ctx.copy_special(src_path, dst_path, rel_path, &meta);
+    }
+
+    if ft.is_dir() {
+        if ctx.level + entry.depth() >= opts.depth {
+            ctx.depth_reached(src_path)?;
+        }
+        if opts.flatten {
+            ctx.track(src_path);
+            return Ok(());
+        }
+        if let Some(sink) = ctx.sink.as_deref_mut() {
+            sink.on_dir(dst_path, &meta)?;
+            ctx.stats.dirs_created += 1;
+            return Ok(());
+        }
+        ctx.track(src_path);
+        let created = clear_conflict(dst_path, true, opts)? || !dst_path.exists();
+        if created {
+            if !opts.dry_run {
+                fs::create_dir_all(dst_path)?;
+            }
+            plan::record(|| CopyOp::CreateDir { src: src_path.to_path_buf(), dst: dst_path.to_path_buf() });
+            ctx.stats.dirs_created += 1;
+        }
+        if opts.dry_run {
+            return Ok(());
+        }
+        if opts.preserve_flags && !created {
+            unlock_flags(dst_path);
+        }
+        if opts.preserve_owner {
+            let (uid, gid) = owner(src_path, &meta, opts)?;
+            copy_owner(unix_fs::chown(dst_path, uid, gid), &mut ctx.stats)?;
+        }
+        if opts.preserve_xattr {
+            copy_xattrs(src_path, dst_path)?;
+        }
+        if opts.preserve_acl {
+            copy_acls(src_path, dst_path, true)?;
+        }
+        if created || opts.preserve_timestamps || opts.fsync || opts.preserve_flags {
+            ctx.dirs.push(PendingDir { src: src_path.to_path_buf(), meta, path: dst_path.to_path_buf(), created });
+        }
+    } else if ft.is_file() {
+        if ctx.filter.is_included(rel_path) {
+            ctx.copy_file(src_path, dst_path, &meta)?;
+            ctx.track(src_path);
+        }
+    } else if ft.is_symlink() {
+        #[cfg(not(feature = "no-follow-symlinks"))]
+        if opts.follow_symlinks {
+            return follow_symlink(src, entry, rel_path, dst_path, &meta, ctx);
+        }
+        ctx.copy_symlink(src_path, dst_path, rel_path, &meta)?;
+    }
+
+    Ok(())
+}
 
-pub use error::CopyError;
-pub use options::CopyOptions;
+// Copies what the symlink `entry` leads to in place of the link. Left out of
+// builds with the `no-follow-symlinks` feature, which thus never dereference
+// a link inside the source.
+#[cfg(not(feature = "no-follow-symlinks"))]
+fn follow_symlink(src: &Path, entry: &Entry, rel_path: &Path, dst_path: &Path, meta: &fs::Metadata,
+    ctx: &mut Context
+) -> Result<(), CopyError> {
+    let opts = ctx.opts;
+    let src_path = entry.path();
+    let resolved = resolve_symlink(src_path);
 
-pub fn copy_recursive(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<(), CopyError> {
-    if !src.exists() {
-        return Err(CopyError::SrcNotFound(src.to_path_buf()));
+    if opts.restrict_symlinks {
+        let root = ctx.src_root.clone().unwrap_or_else(|| src.to_path_buf());
+        let target = lexical_target(src_path)?;
+        let escapes = !target.starts_with(&root)
+            || match &resolved {
+                Ok((real, _)) => !real.canonicalize()?.starts_with(&root),
+                Err(_) => false,
+            };
+        // Reported in `skipped_symlinks`, so only logged at debug level.
+        if escapes {
+            debug!("Skipping symlink outside source {} -> {}",
+                src_path.display(), target.display()
+            );
+            plan::record(|| CopyOp::Skip {
+                src: src_path.to_path_buf(), dst: dst_path.to_path_buf(), reason: SkipReason::OutsideSource
+            });
+            ctx.stats.files_skipped += 1;
+            ctx.stats.skipped_symlinks.push((src_path.to_path_buf(), target));
+            return Ok(());
+        }
     }
 
-    if src.is_file() {
-        let dest_path = if dst.is_dir() {
-            dst.join(src.file_name().unwrap_or_default())
-        } else {
-            dst.to_path_buf()
-        };
-        copy_one(src, &dest_path, opts)?;
+    // Nothing to follow; keep the link itself, which may only dangle
+    // until something gets mounted.
+    if matches!(&resolved, Err(CopyError::Io(e)) if e.kind() == io::ErrorKind::NotFound) {
+        if opts.copy_dangling_symlinks {
+            return ctx.copy_symlink(src_path, dst_path, rel_path, meta);
+        }
+        warn!("Skipping dangling symlink {}", src_path.display());
+        plan::record(|| CopyOp::Skip {
+            src: src_path.to_path_buf(), dst: dst_path.to_path_buf(), reason: SkipReason::Dangling
+        });
+        ctx.stats.files_skipped += 1;
         return Ok(());
     }
 
-    if src.is_dir() {
-        if dst.exists() && !dst.is_dir() {
-            return Err(CopyError::DestNotDir(dst.to_path_buf()));
+    let (target_abs, target_meta) = resolved?;
+
+    let target_ft = target_meta.file_type();
+
+    if target_ft.is_block_device() || target_ft.is_char_device() || target_ft.is_fifo() || target_ft.is_socket() {
+        return ctx.copy_special(src_path, dst_path, rel_path, &target_meta);
+    }
+
+    if target_ft.is_file() {
+        if ctx.filter.is_included(rel_path) {
+            ctx.copy_file(&target_abs, dst_path, &target_meta)?;
+            ctx.track(src_path);
+        }
+    } else if target_ft.is_dir() {
+        // `visited` holds the walks in progress: the source itself and
+        // every followed directory around this one.
+        if opts.max_recursion_depth.is_some_and(|max| ctx.visited.len() > max) {
+            return Err(CopyError::DepthExceeded(src_path.to_path_buf()));
         }
+        ctx.level += entry.depth();
+        let outer = mem::replace(&mut ctx.prefix, rel_path.to_path_buf());
+        let walked = walk_dir(&target_abs, src_path, dst_path, ctx);
+        ctx.prefix = outer;
+        ctx.level -= entry.depth();
+        walked?;
+        ctx.track(src_path);
+    }
+    Ok(())
+}
 
-        let base_dst = if !dst.exists() {
-            fs::create_dir_all(dst)?;
-            dst.to_path_buf()
-        } else if opts.content_only {
-            dst.to_path_buf()
-        } else {
-            dst.join(src.file_name().unwrap_or_default())
-        };
+// Rejects transformed paths that would leave the destination and creates
+// the parents a remapped entry may be missing.
+fn transformed_path(base_dst: &Path, new_rel: &Path, create_parents: bool) -> Result<PathBuf, CopyError> {
+    if new_rel.as_os_str().is_empty() || new_rel.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("path_transform result {} leaves the destination", new_rel.display())
+        ).into());
+    }
+    let path = base_dst.join(new_rel);
+    if create_parents
+        && let Some(parent) = path.parent()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
 
-        if !base_dst.exists() {
-            fs::create_dir_all(&base_dst)?;
+// `buf` is the copy buffer, kept by the caller so that one allocation serves
+// every file a thread copies.
+fn copy_one(src: &Path, dst: &Path, opts: &CopyOptions, shared: &Shared, buf: &mut Vec<u8>, stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    // Opened before anything at `dst` is touched, so skipping an unreadable
+    // source leaves the old destination in place.
+    let input = match retry(opts, || fs::File::open(src)) {
+        Err(e) if skips_unreadable(opts, &e) => {
+            warn!("Skipping unreadable file {}: {}", src.display(), e);
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Unreadable });
+            stats.files_skipped += 1;
+            return Ok(());
         }
+        res => res?,
+    };
 
-        let mut visited = HashSet::new();
-        walk_and_copy(src, &base_dst, opts, &mut visited)?;
+    let existing = match clear_conflict(dst, false, opts)? {
+        true => None,
+        false => fs::metadata(dst).ok(),
+    };
+    let src_meta = input.metadata()?;
+    let same_file = existing.as_ref().is_some_and(|dst_meta| same_inode(&src_meta, dst_meta));
+    // Already what a copy made of hard links would produce.
+    if same_file && opts.link_instead_of_copy {
+        plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Exists });
+        stats.files_skipped += 1;
+        return Ok(());
+    }
+    if opts.metadata_only {
+        return sync_metadata(src, dst, &input, existing.as_ref(), opts, stats);
+    }
+    // Rewriting a clone would break the sharing and double the space used.
+    if opts.skip_if_reflinked
+        && let Some(dst_meta) = &existing
+        && dst_meta.len() == src_meta.len()
+        && shares_extents(&input, dst)?
+    {
+        debug!("Skipping {}, already a clone of {}", dst.display(), src.display());
+        plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Reflinked });
+        stats.files_skipped += 1;
+        return Ok(());
+    }
+    if let Some(dst_meta) = &existing {
+        let conflict = match &opts.on_conflict {
+            Some(on_conflict) => on_conflict(src, dst),
+            None if replaces_file(opts, src, dst, dst_meta)? => Conflict::Overwrite,
+            None => Conflict::Skip,
+        };
+        debug!("Existing destination {}: {:?}", dst.display(), conflict);
+        match conflict {
+            // Writing would destroy the source; left alone it is harmless.
+            Conflict::Overwrite if same_file => return Err(CopyError::SameFile(dst.to_path_buf())),
+            Conflict::Overwrite => {}
+            Conflict::Skip => {
+                plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Exists });
+                stats.files_skipped += 1;
+                return Ok(());
+            }
+            Conflict::Rename(name) => {
+                // Refuse to clobber the new name rather than asking again,
+                // which could go on forever.
+                let renamed = dst.parent().unwrap_or_else(|| Path::new("")).join(name);
+                if fs::symlink_metadata(&renamed).is_ok() {
+                    return Err(CopyError::Io(io::Error::new(io::ErrorKind::AlreadyExists,
+                        format!("rename target exists: {}", renamed.display())
+                    )));
+                }
+                // Only meaningful for a single-file copy; a tree copy sets
+                // its root afterwards.
+                stats.destination = renamed.clone();
+                return copy_one(src, &renamed, opts, shared, buf, stats);
+            }
+            Conflict::Abort => return Err(CopyError::Aborted(dst.to_path_buf())),
+        }
+    }
 
+    if !shared.take_file() {
+        stats.truncated = true;
         return Ok(());
     }
+    shared.charge(src, src_meta.len())?;
+    if opts.dry_run {
+        return dry_run_file(src, dst, opts, stats);
+    }
+    let renamed_into_place = opts.atomic || opts.resume;
+    // Truncating keeps the inode, and with it other hard links and the owner.
+    let in_place = opts.overwrite_in_place
+        && opts.backup == BackupMode::None
+        && !renamed_into_place
+        && !opts.link_instead_of_copy
+        && existing.is_some();
+    if existing.is_some() && opts.preserve_flags {
+        unlock_flags(dst);
+    }
+    if existing.is_some() {
+        match backup_path(dst, opts.backup)? {
+            // Keep the old file reachable under `dst` until the rename lands.
+            Some(backup) if renamed_into_place => {
+                let _ = fs::remove_file(&backup);
+                fs::hard_link(dst, &backup)?;
+            }
+            Some(backup) => fs::rename(dst, &backup)?,
+            None if !renamed_into_place && !in_place => fs::remove_file(dst)?,
+            None => {}
+        }
+    } else if let Some(p) = dst.parent() {
+        fs::create_dir_all(p)?;
+    }
 
-    Err(CopyError::NotSupported(src.to_path_buf()))
+    if opts.link_instead_of_copy {
+        match link_file(src, dst, renamed_into_place && existing.is_some()) {
+            Ok(()) => {
+                stats.hardlinks_created += 1;
+                if let Some(progress) = &opts.progress {
+                    progress(&ProgressEvent::FileFinished { path: src });
+                }
+                return Ok(());
+            }
+            // Across devices the copy goes ahead as if linking was never asked for.
+            Err(e) if sys::is_unsupported(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // Only a file whose size, mode, and owner were seen before can be a
+    // duplicate, so the source is read for its digest only then.
+    let key = DedupKey::new(&src_meta, opts);
+    let digest = if opts.dedup && !in_place && key.size > 0 && shared.copied_like(&key) {
+        buf.resize(buffer_len(opts), 0);
+        Some(hash::hash_file(src, &HashAlgo::Sha256, buf)?)
+    } else {
+        None
+    };
+    let earlier = digest.as_deref().and_then(|digest| shared.copy_of(&key, digest, buf));
+    if let Some(earlier) = earlier {
+        match link_file(&earlier, dst, renamed_into_place && existing.is_some()) {
+            Ok(()) => {
+                stats.files_deduplicated += 1;
+                if let Some(progress) = &opts.progress {
+                    progress(&ProgressEvent::FileFinished { path: src });
+                }
+                return Ok(());
+            }
+            // Another filesystem, a first copy that is gone or immutable, or
+            // one with too many links already: copy as usual.
+            Err(e) if sys::is_unsupported(&e)
+                || matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                    | io::ErrorKind::TooManyLinks) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // New files start out with the permission bits of the source (and the
+    // umask), so others never get more access than the source gives them while
+    // the data is written. The owner keeps read and write access for
+    // verifying; the exact mode is set once the data is in place.
+    let mode = src_meta.mode() & 0o777 | 0o600;
+    if opts.resume {
+        // Unlike a temporary file, the `.part` file outlives a failed copy so
+        // the next attempt can pick it up, unless its contents are wrong.
+        let part = partial_path(dst);
+        let (output, offset) = open_partial(&input, &part, mode, opts, buf)?;
+        let written = write_file(src, &part, input, output, offset, opts, shared, buf, stats)
+            .and_then(|()| fs::rename(&part, dst).map_err(CopyError::from));
+        if let Err(CopyError::VerifyFailed { .. }) = &written {
+            let _ = fs::remove_file(&part);
+        }
+        written?;
+    } else if opts.atomic {
+        let (tmp, output) = create_temp(dst, mode)?;
+        let written = write_file(src, &tmp, input, output, 0, opts, shared, buf, stats)
+            .and_then(|()| fs::rename(&tmp, dst).map_err(CopyError::from));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written?;
+    } else {
+        let output = retry(opts, || {
+            fs::OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(dst)
+        })?;
+        write_file(src, dst, input, output, 0, opts, shared, buf, stats)?;
+        if in_place
+            && !opts.preserve_mode
+            && let Some(dst_meta) = &existing
+        {
+            fs::set_permissions(dst, dst_meta.permissions())?;
+        }
+    }
+    if opts.preserve_flags {
+        copy_flags(src, dst)?;
+    }
+    if opts.dedup && key.size > 0 {
+        shared.remember(key, digest, dst);
+    }
+
+    stats.files_copied += 1;
+    if let Some(progress) = &opts.progress {
+        progress(&ProgressEvent::FileFinished { path: src });
+    }
+    Ok(())
+}
+
+// Whether the data of `dst` lives in the same blocks as that of `src`, as
+// after a reflink copy. Filesystems without `FIEMAP` never share.
+fn shares_extents(src: &fs::File, dst: &Path) -> Result<bool, CopyError> {
+    let extents = |file: &fs::File| match sys::physical_extents(file) {
+        Err(e) if sys::is_unsupported(&e) => Ok(None),
+        res => res.map(Some),
+    };
+    let Some(src_extents) = extents(src)?.filter(|extents| !extents.is_empty()) else {
+        return Ok(false);
+    };
+    let output = match fs::File::open(dst) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Ok(false),
+        res => res?,
+    };
+    Ok(extents(&output)?.is_some_and(|extents| extents == src_extents))
+}
+
+// An absolute `target` inside the source tree, moved to the same place in the
+// copy so the link does not lead back to the original. The canonical
+// `src_root` is what matters, as links are usually made against that.
+fn rebase_target(target: &Path, src_root: &Path, dst_root: &Path) -> io::Result<Option<PathBuf>> {
+    if !target.is_absolute() {
+        return Ok(None);
+    }
+    let Ok(rest) = target.strip_prefix(src_root) else {
+        return Ok(None);
+    };
+    let dst_root = std::path::absolute(dst_root)?;
+    Ok(Some(if rest.as_os_str().is_empty() { dst_root } else { dst_root.join(rest) }))
+}
+
+fn backup_path(dst: &Path, mode: BackupMode) -> io::Result<Option<PathBuf>> {
+    let name = dst.file_name().unwrap_or_default();
+    let dir = match dst.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    match mode {
+        BackupMode::None => Ok(None),
+        BackupMode::Simple => {
+            let mut backup = OsString::from(name);
+            backup.push("~");
+            Ok(Some(dir.join(backup)))
+        }
+        BackupMode::Numbered => {
+            let mut prefix = OsString::from(name);
+            prefix.push(".~");
+            let mut last = 0u64;
+            for entry in fs::read_dir(dir)? {
+                let file_name = entry?.file_name();
+                let index = file_name.as_bytes()
+                    .strip_prefix(prefix.as_bytes())
+                    .and_then(|rest| rest.strip_suffix(b"~"))
+                    .and_then(|digits| std::str::from_utf8(digits).ok())
+                    .and_then(|digits| digits.parse::<u64>().ok());
+                if let Some(index) = index {
+                    last = last.max(index);
+                }
+            }
+            let mut backup = prefix;
+            backup.push(format!("{}~", last + 1));
+            Ok(Some(dir.join(backup)))
+        }
+    }
+}
+
+// With `replace`, the link is made under a temporary name and renamed over
+// `dst`, which still holds the previous file.
+fn link_file(src: &Path, dst: &Path, replace: bool) -> io::Result<()> {
+    if !replace {
+        return fs::hard_link(src, dst);
+    }
+    let (tmp, file) = create_temp(dst, 0o600)?;
+    drop(file);
+    fs::remove_file(&tmp)?;
+    fs::hard_link(src, &tmp)?;
+    fs::rename(&tmp, dst).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+fn create_temp(dst: &Path, mode: u32) -> io::Result<(PathBuf, fs::File)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let dir = dst.parent().unwrap_or(Path::new("."));
+    loop {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp = dir.join(format!(".copytmp-{}-{}-{:08x}", std::process::id(), id, nanos));
+        match fs::OpenOptions::new().write(true).create_new(true).mode(mode).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn partial_path(dst: &Path) -> PathBuf {
+    let mut part = dst.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+// Opens the `.part` file of an interrupted copy and returns how much of it can
+// be kept: everything up to the first buffer that differs from the source, or
+// all of it with `trust_partial`. A file longer than the source starts over.
+fn open_partial(input: &fs::File, part: &Path, mode: u32, opts: &CopyOptions, buf: &mut Vec<u8>
+) -> io::Result<(fs::File, u64)> {
+    let output = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).mode(mode).open(part)?;
+    let (total, len) = (input.metadata()?.len(), output.metadata()?.len());
+    let offset = match len {
+        len if len > total => 0,
+        len if opts.trust_partial => len,
+        len => matching_prefix(input, &output, len, buffer_len(opts), buf)?,
+    };
+    output.set_len(offset)?;
+    Ok((output, offset))
+}
+
+fn matching_prefix(src: &fs::File, part: &fs::File, len: u64, size: usize, buf: &mut Vec<u8>
+) -> io::Result<u64> {
+    buf.resize(2 * size, 0);
+    let (src_buf, part_buf) = buf.split_at_mut(size);
+    let mut offset = 0;
+    while offset < len {
+        let n = size.min((len - offset).try_into().unwrap_or(usize::MAX));
+        src.read_exact_at(&mut src_buf[..n], offset)?;
+        part.read_exact_at(&mut part_buf[..n], offset)?;
+        if src_buf[..n] != part_buf[..n] {
+            break;
+        }
+        offset += n as u64;
+    }
+    Ok(offset)
 }
 
-fn walk_and_copy(src: &Path, dst: &Path, opts: &CopyOptions, visited: &mut HashSet<PathBuf>
+// Data before `offset` is already in `output`, from an interrupted copy.
+#[allow(clippy::too_many_arguments)]
+fn write_file(src: &Path, dst: &Path, mut input: fs::File, mut output: fs::File, offset: u64,
+    opts: &CopyOptions, shared: &Shared, buf: &mut Vec<u8>, stats: &mut CopyStats
 ) -> Result<(), CopyError> {
-    let real_src = src.to_path_buf();
+    let src_meta = input.metadata()?;
+    let total = src_meta.len();
 
-    if !visited.insert(real_src.clone()) {
-        return Err(CopyError::SymlinkLoop(real_src));
+    if let Some(progress) = &opts.progress {
+        progress(&ProgressEvent::FileStarted { path: src, size: total });
     }
 
-    let walker = WalkDir::new(src)?.max_depth(opts.depth);
-    for entry_res in walker {
-        let entry = entry_res.map_err(CopyError::Walk)?;
-        let src_path = entry.path();
-        let rel_part = src_path.strip_prefix(src).unwrap_or(src_path);
-        let dst_path = dst.join(rel_part);
-        let meta = entry.symlink_metadata().map_err(CopyError::Io)?;
-        let ft = meta.file_type();
+    let cloned = match opts.reflink {
+        ReflinkMode::Never => false,
+        mode => match sys::reflink(&input, &output) {
+            Ok(()) => true,
+            Err(e) if sys::is_unsupported(&e) => {
+                if mode == ReflinkMode::Always {
+                    drop(output);
+                    fs::remove_file(dst)?;
+                    return Err(CopyError::ReflinkNotSupported(src.to_path_buf()));
+                }
+                false
+            }
+            Err(e) => return Err(e.into()),
+        },
+    };
 
-        if ft.is_block_device() || ft.is_char_device() || ft.is_fifo() || ft.is_socket() {
-            continue;
+    buf.resize(buffer_len(opts), 0);
+    let mut hasher = None;
+    if cloned {
+        stats.bytes_copied += total;
+        if let Some(progress) = &opts.progress {
+            progress(&ProgressEvent::BytesCopied { path: src, so_far: total, total });
+        }
+    } else {
+        if let VerifyMode::Checksum(algo) = &opts.verify {
+            hasher = Some(hash::hasher(algo));
         }
 
-        if ft.is_dir() {
-            if !dst_path.exists() {
-                fs::create_dir_all(&dst_path)?;
+        // Sources that already have holes are copied extent by extent; the
+        // whole file is a single extent otherwise.
+        let mut extents = None;
+        if opts.sparse == SparseMode::Auto && src_meta.blocks() * 512 < total {
+            match sys::data_extents(&input, total) {
+                Ok(found) => extents = Some(found),
+                Err(e) if sys::is_unsupported(&e) => {}
+                Err(e) => return Err(e.into()),
             }
-        } else if ft.is_file() {
-            copy_one(src_path, &dst_path, opts)?;
-        } else if ft.is_symlink() {
-            if opts.follow_symlinks {
-                let target = fs::read_link(src_path)?;
-                let target_abs = if target.is_absolute() {
-                    target.clone()
-                } else {
-                    src_path.parent().unwrap_or_else(|| Path::new("/")).join(&target)
-                };
+        }
+        let sparse_file = extents.is_some();
+        let extents = extents.unwrap_or_else(|| vec![(0, u64::MAX)]);
 
-                if opts.restrict_symlinks {
-                    if let (Ok(base_real), Ok(target_real)) = (src.canonicalize(), target_abs.canonicalize()) {
-                        if !target_real.starts_with(&base_real) {
-                            eprintln!("Skipping symlink outside source {} -> {}",
-                                src_path.display(), target_real.display()
-                            );
-                            continue;
-                        }
-                    }
-                }
+        // Reserving the full length would fill the holes sparse copies keep.
+        if opts.preallocate && opts.sparse == SparseMode::Never && total > 0 {
+            match sys::preallocate(&output, total) {
+                Err(e) if !sys::is_unsupported(&e) => return Err(e.into()),
+                _ => {}
+            }
+        }
 
-                let target_meta = target_abs.symlink_metadata().map_err(CopyError::Io)?;
-                let target_ft = target_meta.file_type();
+        let offset = if opts.copy_file_range && !sparse_file && opts.sparse != SparseMode::Always {
+            copy_range(src, &input, &output, offset, total, opts, shared, stats)?
+        } else {
+            offset
+        };
 
-                if target_ft.is_block_device() || target_ft.is_char_device() || target_ft.is_fifo() || target_ft.is_socket() {
-                    continue;
+        // Data before `offset` is already in place: kept from an interrupted
+        // copy or copied by the kernel. It still counts towards the checksum.
+        let mut so_far = offset;
+        if offset > 0 {
+            input.seek(SeekFrom::Start(offset))?;
+            output.seek(SeekFrom::Start(offset))?;
+            if let Some(hasher) = &mut hasher {
+                let mut pos = 0;
+                while pos < offset {
+                    let n = buf.len().min((offset - pos).try_into().unwrap_or(usize::MAX));
+                    input.read_exact_at(&mut buf[..n], pos)?;
+                    hasher.update(&buf[..n]);
+                    pos += n as u64;
                 }
-
-                if target_ft.is_file() {
-                    copy_one(&target_abs, &dst_path, opts)?;
-                } else if target_ft.is_dir() {
-                    walk_and_copy(&target_abs, &dst_path, opts, visited)?;
+            }
+        }
+        let mut holes = false;
+        for (start, end) in extents {
+            if start > so_far {
+                skip_hole(&mut input, &mut output, start - so_far, hasher.as_mut(), buf)?;
+                holes = true;
+                so_far = start;
+            }
+            while so_far < end {
+                opts.check_cancelled()?;
+                let want = buf.len().min((end - so_far).try_into().unwrap_or(usize::MAX));
+                let n = match retry(opts, || input.read(&mut buf[..want]))? {
+                    0 => break,
+                    n => n,
+                };
+                if opts.sparse == SparseMode::Always {
+                    holes |= write_sparse(&mut output, &buf[..n])?;
+                } else {
+                    write_retrying(opts, &mut output, &buf[..n])?;
+                }
+                shared.throttle(n as u64);
+                if let Some(hasher) = &mut hasher {
+                    hasher.update(&buf[..n]);
+                }
+                stats.bytes_copied += n as u64;
+                so_far += n as u64;
+                if let Some(progress) = &opts.progress {
+                    progress(&ProgressEvent::BytesCopied { path: src, so_far, total });
                 }
-            } else {
-                recreate_symlink(src_path, &dst_path, opts)?;
             }
         }
+        if sparse_file && total > so_far {
+            skip_hole(&mut input, &mut output, total - so_far, hasher.as_mut(), buf)?;
+            holes = true;
+            so_far = total;
+        }
+        if holes {
+            output.set_len(so_far)?;
+        }
     }
-    visited.remove(&real_src);
+
+    verify_copy(src, dst, &output, total, hasher, buf, opts)?;
+    apply_metadata(src, dst, &output, &src_meta, opts, stats)?;
+
+    if opts.fsync {
+        output.sync_all()?;
+    }
+
     Ok(())
 }
 
-
-fn copy_one(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<(), CopyError> {
-    if dst.exists() {
-        if !opts.overwrite {
-            return Ok(());
+// Copies inside the kernel from `offset` on, in chunks so that cancellation,
+// throttling, and progress still apply, and returns how far it got. When the
+// filesystems cannot (`EXDEV`, `ENOSYS`, or a premature end as on procfs),
+// the buffered loop carries on from there.
+#[allow(clippy::too_many_arguments)]
+fn copy_range(src: &Path, input: &fs::File, output: &fs::File, mut so_far: u64, total: u64, opts: &CopyOptions,
+    shared: &Shared, stats: &mut CopyStats
+) -> Result<u64, CopyError> {
+    while so_far < total {
+        opts.check_cancelled()?;
+        let n = match sys::copy_range(input, output, so_far, (total - so_far).min(COPY_RANGE_CHUNK)) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if sys::is_unsupported(&e) => break,
+            Err(e) => return Err(e.into()),
+        };
+        shared.throttle(n);
+        stats.bytes_copied += n;
+        so_far += n;
+        if let Some(progress) = &opts.progress {
+            progress(&ProgressEvent::BytesCopied { path: src, so_far, total });
         }
-        fs::remove_file(dst)?;
-    } else if let Some(p) = dst.parent() {
-        fs::create_dir_all(p)?;
     }
+    Ok(so_far)
+}
 
-    let mut input = fs::File::open(src)?;
-    let mut output = fs::File::create(dst)?;
-    copy(&mut input, &mut output)?;
+// `output` is an open handle on `dst`, the destination of the file `src`.
+fn apply_metadata(src: &Path, dst: &Path, output: &fs::File, src_meta: &fs::Metadata, opts: &CopyOptions,
+    stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    if opts.preserve_owner {
+        let (uid, gid) = owner(src, src_meta, opts)?;
+        copy_owner(unix_fs::fchown(output, uid, gid), stats)?;
+    }
 
-    let mode = fs::metadata(src)?.permissions().mode() & 0o777;
+    let mode = src_meta.permissions().mode() & mode_mask(opts);
     let mut perms = output.metadata()?.permissions();
     perms.set_mode(mode);
     fs::set_permissions(dst, perms)?;
 
+    if opts.preserve_xattr {
+        copy_xattrs(src, dst)?;
+    }
+    if opts.preserve_acl {
+        copy_acls(src, dst, false)?;
+    }
+
+    if opts.preserve_timestamps {
+        output.set_times(file_times(src_meta)?)?;
+    }
     Ok(())
 }
 
-fn recreate_symlink(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<(), CopyError> {
-    let target = fs::read_link(src)?;
-    if dst.exists() {
-        if opts.overwrite {
-            fs::remove_file(dst)?;
+// Repairs the metadata of a destination whose data is taken to be complete
+// because its size matches; anything else is left for a real copy.
+fn sync_metadata(src: &Path, dst: &Path, input: &fs::File, existing: Option<&fs::Metadata>, opts: &CopyOptions,
+    stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    let src_meta = input.metadata()?;
+    if !existing.is_some_and(|dst_meta| dst_meta.is_file() && dst_meta.len() == src_meta.len()) {
+        plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::SizeMismatch });
+        stats.files_skipped += 1;
+        return Ok(());
+    }
+    plan::record(|| CopyOp::SyncMetadata { src: src.to_path_buf(), dst: dst.to_path_buf() });
+    if !opts.dry_run {
+        if opts.preserve_flags {
+            unlock_flags(dst);
+        }
+        let output = fs::File::open(dst)?;
+        apply_metadata(src, dst, &output, &src_meta, opts, stats)?;
+        if opts.preserve_flags {
+            copy_flags(src, dst)?;
+        }
+    }
+    stats.metadata_synced += 1;
+    Ok(())
+}
+
+// Runs `op` again on `EINTR` without counting it, and on errors that tend to
+// clear up by themselves (timeouts, `EAGAIN`) up to `opts.retries` times.
+fn retry<T>(opts: &CopyOptions, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempts = 0;
+    loop {
+        match op() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if attempts < opts.retries
+                && matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) =>
+            {
+                attempts += 1;
+                thread::sleep(opts.retry_delay);
+            }
+            res => return res,
+        }
+    }
+}
+
+// Like `write_all`, but a retried write resumes after the bytes that already
+// went out instead of repeating them.
+fn write_retrying(opts: &CopyOptions, output: &mut fs::File, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match retry(opts, || output.write(data))? {
+            0 => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+            n => data = &data[n..],
+        }
+    }
+    Ok(())
+}
+
+fn write_sparse(output: &mut fs::File, data: &[u8]) -> io::Result<bool> {
+    let mut seeked = false;
+    for block in data.chunks(SPARSE_BLOCK) {
+        if block.iter().all(|&b| b == 0) {
+            output.seek(SeekFrom::Current(block.len() as i64))?;
+            seeked = true;
         } else {
+            output.write_all(block)?;
+        }
+    }
+    Ok(seeked)
+}
+
+fn skip_hole(input: &mut fs::File, output: &mut fs::File, len: u64, hasher: Option<&mut Box<dyn Hasher>>,
+    buf: &mut [u8]
+) -> io::Result<()> {
+    input.seek(SeekFrom::Current(len as i64))?;
+    output.seek(SeekFrom::Current(len as i64))?;
+    if let Some(hasher) = hasher {
+        buf.fill(0);
+        let mut left = len;
+        while left > 0 {
+            let n = buf.len().min(left.try_into().unwrap_or(usize::MAX));
+            hasher.update(&buf[..n]);
+            left -= n as u64;
+        }
+    }
+    Ok(())
+}
+
+fn verify_copy(
+    src: &Path,
+    dst: &Path,
+    output: &fs::File,
+    total: u64,
+    hasher: Option<Box<dyn Hasher>>,
+    buf: &mut [u8],
+    opts: &CopyOptions,
+) -> Result<(), CopyError> {
+    let (expected, found) = match &opts.verify {
+        VerifyMode::None => return Ok(()),
+        VerifyMode::Size => {
+            let found = output.metadata()?.len();
+            (format!("{total} bytes"), format!("{found} bytes"))
+        }
+        VerifyMode::Checksum(algo) => {
+            // Buffered copies hashed the source while writing; a reflink never
+            // read it, so hash it now.
+            let expected = match hasher {
+                Some(hasher) => hash::finish(hasher),
+                None => hash::hash_file(src, algo, buf)?,
+            };
+            (expected, hash::hash_file(dst, algo, buf)?)
+        }
+    };
+
+    if expected != found {
+        return Err(CopyError::VerifyFailed { path: src.to_path_buf(), expected, found });
+    }
+    Ok(())
+}
+
+fn should_replace(opts: &CopyOptions, src_meta: &fs::Metadata, dst_meta: &fs::Metadata) -> bool {
+    match opts.resolved_overwrite() {
+        OverwriteMode::Never => false,
+        OverwriteMode::Always => true,
+        OverwriteMode::IfNewer => match (src_meta.modified(), dst_meta.modified()) {
+            (Ok(src_time), Ok(dst_time)) => src_time > dst_time,
+            _ => true,
+        },
+        OverwriteMode::IfDifferentSize => src_meta.len() != dst_meta.len(),
+        // Only regular files are compared by content, in `replaces_file`.
+        OverwriteMode::IfDifferent => true,
+    }
+}
+
+fn replaces_file(opts: &CopyOptions, src: &Path, dst: &Path, dst_meta: &fs::Metadata) -> io::Result<bool> {
+    let src_meta = fs::metadata(src)?;
+    if opts.resolved_overwrite() == OverwriteMode::IfDifferent && dst_meta.is_file() {
+        return Ok(src_meta.len() != dst_meta.len() || !same_contents(src, dst, buffer_len(opts))?);
+    }
+    Ok(should_replace(opts, &src_meta, dst_meta))
+}
+
+// Streams both files side by side; callers have already checked that the
+// sizes match.
+fn same_contents(src: &Path, dst: &Path, size: usize) -> io::Result<bool> {
+    let (mut src, mut dst) = (fs::File::open(src)?, fs::File::open(dst)?);
+    let (mut src_buf, mut dst_buf) = (vec![0u8; size], vec![0u8; size]);
+    loop {
+        let n = match src.read(&mut src_buf) {
+            Ok(0) => return Ok(dst.read(&mut dst_buf)? == 0),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        match dst.read_exact(&mut dst_buf[..n]) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            res => res?,
+        }
+        if src_buf[..n] != dst_buf[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+fn dry_run_file(src: &Path, dst: &Path, opts: &CopyOptions, stats: &mut CopyStats) -> Result<(), CopyError> {
+    let size = fs::metadata(src)?.len();
+    plan::record(|| CopyOp::CopyFile { src: src.to_path_buf(), dst: dst.to_path_buf() });
+    stats.bytes_copied += size;
+    stats.files_copied += 1;
+    if let Some(progress) = &opts.progress {
+        progress(&ProgressEvent::FileStarted { path: src, size });
+        progress(&ProgressEvent::FileFinished { path: src });
+    }
+    Ok(())
+}
+
+fn file_times(meta: &fs::Metadata) -> io::Result<FileTimes> {
+    Ok(FileTimes::new().set_accessed(meta.accessed()?).set_modified(meta.modified()?))
+}
+
+fn copy_times(meta: &fs::Metadata, dst: &Path) -> io::Result<()> {
+    match sys::set_times_nofollow(dst, meta) {
+        // Targets without `utimensat` keep the times the copy got.
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            debug!("Not preserving the times of {}: {}", dst.display(), e);
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+// `0` would never make progress and huge values are allocated once per
+// thread, so the size asked for is kept within sane bounds.
+fn buffer_len(opts: &CopyOptions) -> usize {
+    opts.buffer_size.clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE)
+}
+
+// Setuid/setgid are dropped by the kernel on `chown`, so callers apply the
+// mode only once ownership has been set.
+fn mode_mask(opts: &CopyOptions) -> u32 {
+    if opts.preserve_mode { 0o7777 } else { 0o777 }
+}
+
+// Files that cannot be opened or vanished mid-walk; anything else, such as a
+// full disk, still fails the copy.
+fn skips_unreadable(opts: &CopyOptions, e: &io::Error) -> bool {
+    opts.skip_unreadable && matches!(e.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound)
+}
+
+// The owner given to the copy of `src`: its own uid and gid, translated
+// through `uid_map` and `gid_map`. With `strict_id_map`, an id missing from a
+// non-empty map is an error rather than kept as is.
+fn owner(src: &Path, meta: &fs::Metadata, opts: &CopyOptions) -> Result<(Option<u32>, Option<u32>), CopyError> {
+    let map = |map: &HashMap<u32, u32>, id: u32| match map.get(&id) {
+        Some(&mapped) => Ok(mapped),
+        None if opts.strict_id_map && !map.is_empty() => Err(id),
+        None => Ok(id),
+    };
+    match (map(&opts.uid_map, meta.uid()), map(&opts.gid_map, meta.gid())) {
+        (Ok(uid), Ok(gid)) => Ok((Some(uid), Some(gid))),
+        (uid, gid) => Err(CopyError::UnmappedOwner { path: src.to_path_buf(), uid: uid.err(), gid: gid.err() }),
+    }
+}
+
+fn copy_owner(res: io::Result<()>, stats: &mut CopyStats) -> Result<(), CopyError> {
+    match res {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            stats.owners_not_preserved += 1;
+            Ok(())
+        }
+        res => Ok(res?),
+    }
+}
+
+fn create_special(src: &Path, meta: &fs::Metadata, dst: &Path, opts: &CopyOptions, stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    let ft = meta.file_type();
+    if !clear_conflict(dst, false, opts)?
+        && let Ok(dst_meta) = dst.symlink_metadata()
+    {
+        if !should_replace(opts, meta, &dst_meta) {
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Exists });
+            stats.files_skipped += 1;
+            return Ok(());
+        }
+        if !opts.dry_run {
+            fs::remove_file(dst)?;
+        }
+    }
+
+    if opts.dry_run {
+        plan::record(|| CopyOp::Special { src: src.to_path_buf(), dst: dst.to_path_buf() });
+        stats.specials_created += 1;
+        return Ok(());
+    }
+
+    if let Some(p) = dst.parent() {
+        fs::create_dir_all(p)?;
+    }
+
+    let mode = meta.mode() & mode_mask(opts);
+    if ft.is_fifo() {
+        sys::make_fifo(dst, mode & 0o777)?;
+    } else {
+        sys::make_node(dst, meta.mode() & (sys::S_IFMT | 0o777), meta.rdev())?;
+    }
+
+    if opts.preserve_owner {
+        let (uid, gid) = owner(src, meta, opts)?;
+        copy_owner(unix_fs::lchown(dst, uid, gid), stats)?;
+    }
+    fs::set_permissions(dst, fs::Permissions::from_mode(mode))?;
+    if opts.preserve_acl {
+        copy_acls(src, dst, false)?;
+    }
+
+    if opts.preserve_timestamps {
+        copy_times(meta, dst)?;
+    }
+
+    stats.specials_created += 1;
+    Ok(())
+}
+
+fn copy_xattrs(src: &Path, dst: &Path) -> Result<(), CopyError> {
+    let names = match sys::list_xattrs(src) {
+        Err(e) if sys::is_unsupported(&e) => return Ok(()),
+        res => res?,
+    };
+
+    for name in names {
+        let value = match sys::get_xattr(src, &name) {
+            Err(e) if e.raw_os_error() == Some(sys::ENODATA) => continue,
+            res => res?,
+        };
+        match sys::set_xattr(dst, &name, &value) {
+            Err(e) if sys::is_unsupported(&e) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => continue,
+            res => res?,
+        }
+    }
+    Ok(())
+}
+
+// POSIX ACLs are stored in `system.posix_acl_*` xattrs, which carry the
+// same data `acl_get_file`/`acl_set_file` exchange, so libacl is not needed.
+// An ACL has to be set after the mode: `chmod` rewrites its mask entry. The
+// later `chmod` of a created directory applies the source's group bits, which
+// are that mask, so it leaves the copied ACL intact.
+fn copy_acls(src: &Path, dst: &Path, is_dir: bool) -> Result<(), CopyError> {
+    let names: &[&CStr] = if is_dir { &[sys::ACL_ACCESS, sys::ACL_DEFAULT] } else { &[sys::ACL_ACCESS] };
+    for name in names {
+        let value = match sys::get_xattr(src, name) {
+            Err(e) if e.raw_os_error() == Some(sys::ENODATA) => continue,
+            Err(e) if sys::is_unsupported(&e) => return Ok(()),
+            res => res?,
+        };
+        match sys::set_xattr(dst, name, &value) {
+            Err(e) if sys::is_unsupported(&e) => return Ok(()),
+            res => res?,
+        }
+    }
+    Ok(())
+}
+
+// `roots` are the source and destination roots of the tree being copied,
+// given with `rewrite_symlink_targets`.
+fn recreate_symlink(src: &Path, dst: &Path, roots: Option<(&Path, &Path)>, opts: &CopyOptions,
+    stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    let mut target = fs::read_link(src)?;
+    if let Some((src_root, dst_root)) = roots
+        && let Some(rebased) = rebase_target(&target, src_root, dst_root)?
+    {
+        debug!("Rewriting link target of {}: {} -> {}", dst.display(), target.display(), rebased.display());
+        target = rebased;
+    }
+    if !clear_conflict(dst, false, opts)?
+        && let Ok(dst_meta) = fs::metadata(dst)
+    {
+        if !should_replace(opts, &fs::symlink_metadata(src)?, &dst_meta) {
+            plan::record(|| CopyOp::Skip { src: src.to_path_buf(), dst: dst.to_path_buf(), reason: SkipReason::Exists });
+            stats.files_skipped += 1;
             return Ok(());
         }
+        if !opts.dry_run {
+            fs::remove_file(dst)?;
+        }
+    }
+
+    if opts.dry_run {
+        plan::record(|| CopyOp::Symlink { src: src.to_path_buf(), dst: dst.to_path_buf() });
+        stats.symlinks_created += 1;
+        return Ok(());
     }
 
     if let Some(p) = dst.parent() {
@@ -159,6 +2202,22 @@ fn recreate_symlink(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<(), Co
     }
 
     unix_fs::symlink(&target, dst)?;
+    // The link's own metadata; the target's is applied when it gets copied.
+    let meta = fs::symlink_metadata(src)?;
+    if opts.preserve_owner {
+        let (uid, gid) = owner(src, &meta, opts)?;
+        copy_owner(unix_fs::lchown(dst, uid, gid), stats)?;
+    }
+    if opts.preserve_mode {
+        match sys::chmod_nofollow(dst, meta.mode() & mode_mask(opts)) {
+            Err(e) if !sys::is_unsupported(&e) => return Err(e.into()),
+            _ => {}
+        }
+    }
+    if opts.preserve_xattr {
+        copy_xattrs(src, dst)?;
+    }
+    stats.symlinks_created += 1;
     Ok(())
 }
 
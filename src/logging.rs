@@ -0,0 +1,24 @@
+// Messages go through the `log` facade with the `log` feature. Without it,
+// they are dropped; the library never writes to stderr itself.
+
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
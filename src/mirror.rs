@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use walkdir_minimal::WalkDir;
+
+use crate::filter::Filter;
+use crate::plan::{self, CopyOp};
+use crate::{CopyError, CopyOptions, CopyStats};
+
+// Removes entries under `base_dst` that have no counterpart in `src`. Excluded
+// subtrees are never looked at, so they survive like with `rsync --delete`.
+pub(crate) fn delete_extraneous(src: &Path, base_dst: &Path, filter: &Arc<Filter>, opts: &CopyOptions,
+    stats: &mut CopyStats
+) -> Result<(), CopyError> {
+    // A dry run (or `plan`) into a missing destination never created it, so
+    // there is nothing there to delete.
+    if opts.dry_run && fs::symlink_metadata(base_dst).is_err() {
+        return Ok(());
+    }
+    let extraneous = Rc::new(RefCell::new(Vec::new()));
+    let found = Rc::clone(&extraneous);
+    let filter = Arc::clone(filter);
+    let (src_root, dst_root) = (src.to_path_buf(), base_dst.to_path_buf());
+    let respect_ignore_files = opts.respect_ignore_files;
+
+    let walker = WalkDir::new(base_dst)?.filter_entry(move |e| {
+        let rel = e.path().strip_prefix(&dst_root).unwrap_or(e.path());
+        if filter.is_excluded(rel)
+            || respect_ignore_files && filter.is_ignored(rel, e.file_type().is_ok_and(|t| t.is_dir()))
+        {
+            return false;
+        }
+        if fs::symlink_metadata(src_root.join(rel)).is_err() {
+            found.borrow_mut().push(e.path().to_path_buf());
+            return false;
+        }
+        true
+    });
+    for entry in walker {
+        entry?;
+    }
+
+    // Extraneous directories are pruned from the walk and removed whole,
+    // `remove_dir_all` emptying them bottom-up.
+    for path in extraneous.take() {
+        plan::record(|| CopyOp::Delete { dst: path.clone() });
+        if !opts.dry_run {
+            if fs::symlink_metadata(&path)?.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        stats.entries_deleted += 1;
+    }
+    Ok(())
+}
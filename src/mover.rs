@@ -0,0 +1,92 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{CopyError, CopyFailure, CopyOptions, CopyStats, check_dst_symlink, copy_tree};
+
+pub fn move_recursive(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<CopyStats, CopyFailure> {
+    let failed = |error: CopyError| CopyFailure { stats: Box::default(), error };
+    let Ok(src_meta) = fs::symlink_metadata(src) else {
+        return Err(failed(CopyError::SrcNotFound(src.to_path_buf())));
+    };
+    check_dst_symlink(dst, opts).map_err(failed)?;
+    // Like `mv`, a symlink is moved as the link itself; copying what it points
+    // to would have the source removed through it.
+    let link_opts;
+    let opts = if src_meta.file_type().is_symlink() {
+        link_opts = CopyOptions { follow_top_level_symlink: false, follow_symlinks: false, ..opts.clone() };
+        &link_opts
+    } else {
+        opts
+    };
+
+    if !opts.dry_run && renames_cleanly(&src_meta, opts) {
+        let target = if opts.lands_inside(src, dst) {
+            dst.join(src.file_name().unwrap_or_default())
+        } else {
+            dst.to_path_buf()
+        };
+        // Any failure here (EXDEV, a missing parent, ...) falls back to the
+        // copy, which reports its own errors.
+        if fs::symlink_metadata(&target).is_err() && fs::rename(src, &target).is_ok() {
+            return Ok(CopyStats {
+                destination: target,
+                ..Default::default()
+            });
+        }
+    }
+
+    let (stats, copied) = copy_tree(src, dst, opts, true)?;
+    // Metadata-only passes leave the data where it was.
+    if !opts.dry_run && !opts.metadata_only && stats.errors.is_empty() && stats.files_skipped == 0
+        && !stats.truncated
+    {
+        return CopyFailure::with_stats(stats, remove_source(&copied));
+    }
+    Ok(stats)
+}
+
+// A rename moves the tree as is, so it is only taken when no option would
+// make the copy differ from the source.
+fn renames_cleanly(src_meta: &fs::Metadata, opts: &CopyOptions) -> bool {
+    let sized = opts.max_file_size.is_none() && opts.min_file_size.is_none() && opts.newer_than.is_none()
+        && opts.max_files.is_none() && !opts.metadata_only;
+    if !src_meta.is_dir() {
+        return sized;
+    }
+    sized
+        && opts.include.is_empty()
+        && opts.exclude.is_empty()
+        && !opts.skip_hidden
+        && !opts.respect_ignore_files
+        && !opts.flatten
+        && !opts.prune_empty_dirs
+        && opts.path_transform.is_none()
+        && opts.on_dir.is_none()
+        && !opts.follows_symlinks()
+        && !opts.rewrite_symlink_targets
+        && !opts.one_file_system
+        && opts.depth >= CopyOptions::default().depth
+        && opts.min_depth == 0
+}
+
+fn remove_source(copied: &[PathBuf]) -> Result<(), CopyError> {
+    for path in copied.iter().rev() {
+        let meta = fs::symlink_metadata(path)?;
+        if !meta.is_dir() {
+            fs::remove_file(path)?;
+            continue;
+        }
+        // Directories still holding entries that were not copied (filtered,
+        // too deep, ...) are left in place.
+        match fs::remove_dir(path) {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::DirectoryNotEmpty | io::ErrorKind::ResourceBusy
+                ) => {}
+            res => res?,
+        }
+    }
+    Ok(())
+}
@@ -1,22 +1,824 @@
-#[derive(Clone, Debug)]
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::CopyError;
+use crate::hash::Hasher;
+use crate::progress::{ProgressCallback, ProgressEvent};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReflinkMode {
+    #[default]
+    Never,
+    Auto,
+    Always,
+}
+
+// Where `src` ends up relative to `dst`. `Auto` infers it like `cp` does,
+// from whether `dst` is a directory, `content_only`, and a trailing `/`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DestMode {
+    #[default]
+    Auto,
+    As,
+    Into,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverwriteMode {
+    #[default]
+    Never,
+    Always,
+    IfNewer,
+    IfDifferentSize,
+    IfDifferent,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackupMode {
+    #[default]
+    None,
+    Simple,
+    Numbered,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    Overwrite,
+    Skip,
+    Rename(PathBuf),
+    Abort,
+}
+
+pub type ConflictCallback = Arc<dyn Fn(&Path, &Path) -> Conflict + Send + Sync>;
+
+pub type PathTransform = Arc<dyn Fn(&Path) -> Option<PathBuf> + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirAction {
+    Descend,
+    Skip,
+    SkipRecursive,
+}
+
+pub type DirCallback = Arc<dyn Fn(&Path, &Path) -> DirAction + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SparseMode {
+    #[default]
+    Never,
+    Auto,
+    Always,
+}
+
+pub type HasherFactory = Arc<dyn Fn() -> Box<dyn Hasher> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub enum HashAlgo {
+    Crc32,
+    #[default]
+    Sha256,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    // Called once per file hashed.
+    Custom(HasherFactory),
+}
+
+impl fmt::Debug for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgo::Crc32 => f.write_str("Crc32"),
+            HashAlgo::Sha256 => f.write_str("Sha256"),
+            #[cfg(feature = "blake3")]
+            HashAlgo::Blake3 => f.write_str("Blake3"),
+            HashAlgo::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+// Custom algorithms are only equal to clones of themselves.
+impl PartialEq for HashAlgo {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (HashAlgo::Custom(a), HashAlgo::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for HashAlgo {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum VerifyMode {
+    #[default]
+    None,
+    Size,
+    Checksum(HashAlgo),
+}
+
+#[derive(Clone)]
+#[non_exhaustive]
 pub struct CopyOptions {
     pub overwrite: bool,
+    pub overwrite_mode: OverwriteMode,
+    pub overwrite_in_place: bool,
+    pub backup: BackupMode,
+    pub replace_dest: bool,
+    pub detect_case_collisions: bool,
+    pub on_conflict: Option<ConflictCallback>,
     pub restrict_symlinks: bool,
+    pub rewrite_symlink_targets: bool,
     pub follow_symlinks: bool,
+    pub follow_top_level_symlink: bool,
+    pub follow_dst_symlink: bool,
+    pub create_parents: bool,
+    pub copy_dangling_symlinks: bool,
     pub content_only: bool,
+    pub dest_mode: DestMode,
+    pub flatten: bool,
+    pub one_file_system: bool,
+    pub preserve_timestamps: bool,
+    pub preserve_owner: bool,
+    pub uid_map: HashMap<u32, u32>,
+    pub gid_map: HashMap<u32, u32>,
+    pub strict_id_map: bool,
+    pub preserve_mode: bool,
+    pub preserve_xattr: bool,
+    pub preserve_acl: bool,
+    pub preserve_flags: bool,
+    pub preserve_hardlinks: bool,
+    pub reflink: ReflinkMode,
+    pub copy_file_range: bool,
+    pub skip_if_reflinked: bool,
+    pub link_instead_of_copy: bool,
+    pub dedup: bool,
+    pub metadata_only: bool,
+    pub sparse: SparseMode,
+    pub preallocate: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub skip_hidden: bool,
+    pub respect_ignore_files: bool,
+    pub path_transform: Option<PathTransform>,
+    pub on_dir: Option<DirCallback>,
+    pub delete_extraneous: bool,
+    pub prune_empty_dirs: bool,
+    pub max_file_size: Option<u64>,
+    pub min_file_size: Option<u64>,
+    pub newer_than: Option<SystemTime>,
+    pub max_total_bytes: Option<u64>,
+    pub max_files: Option<usize>,
+    pub copy_special: bool,
+    pub strict: bool,
+    pub dry_run: bool,
+    pub atomic: bool,
+    pub resume: bool,
+    pub trust_partial: bool,
+    pub fsync: bool,
+    pub verify: VerifyMode,
+    pub continue_on_error: bool,
+    pub skip_unreadable: bool,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub progress: Option<ProgressCallback>,
+    pub cancel: Option<Arc<AtomicBool>>,
+    pub deadline: Option<Instant>,
+    pub threads: usize,
     pub buffer_size: usize,
+    pub max_bytes_per_sec: Option<u64>,
     pub depth: usize,
+    pub min_depth: usize,
+    pub sort_by_name: bool,
+    pub max_recursion_depth: Option<usize>,
+    pub fail_on_depth: bool,
 }
 
 impl Default for CopyOptions {
     fn default() -> Self {
         Self {
             overwrite: false,
+            overwrite_mode: OverwriteMode::Never,
+            overwrite_in_place: false,
+            backup: BackupMode::None,
+            replace_dest: false,
+            detect_case_collisions: false,
+            on_conflict: None,
             restrict_symlinks: false,
+            rewrite_symlink_targets: false,
             follow_symlinks: false,
+            follow_top_level_symlink: true,
+            follow_dst_symlink: true,
+            create_parents: true,
+            copy_dangling_symlinks: true,
             content_only: false,
+            dest_mode: DestMode::Auto,
+            flatten: false,
+            one_file_system: false,
+            preserve_timestamps: false,
+            preserve_owner: false,
+            uid_map: HashMap::new(),
+            gid_map: HashMap::new(),
+            strict_id_map: false,
+            preserve_mode: false,
+            preserve_xattr: false,
+            preserve_acl: false,
+            preserve_flags: false,
+            preserve_hardlinks: false,
+            reflink: ReflinkMode::Never,
+            copy_file_range: false,
+            skip_if_reflinked: false,
+            link_instead_of_copy: false,
+            dedup: false,
+            metadata_only: false,
+            sparse: SparseMode::Never,
+            preallocate: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            skip_hidden: false,
+            respect_ignore_files: false,
+            path_transform: None,
+            on_dir: None,
+            delete_extraneous: false,
+            prune_empty_dirs: false,
+            max_file_size: None,
+            min_file_size: None,
+            newer_than: None,
+            max_total_bytes: None,
+            max_files: None,
+            copy_special: false,
+            strict: false,
+            dry_run: false,
+            atomic: false,
+            resume: false,
+            trust_partial: false,
+            fsync: false,
+            verify: VerifyMode::None,
+            continue_on_error: false,
+            skip_unreadable: false,
+            retries: 0,
+            retry_delay: Duration::from_millis(100),
+            progress: None,
+            cancel: None,
+            deadline: None,
+            threads: 1,
             buffer_size: 64 * 1024,
+            max_bytes_per_sec: None,
             depth: 512,
+            min_depth: 0,
+            sort_by_name: false,
+            max_recursion_depth: None,
+            fail_on_depth: false,
+        }
+    }
+}
+
+impl CopyOptions {
+    pub(crate) fn worker_threads(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            n => n,
+        }
+    }
+
+    // Flattened or remapped entries have no counterpart in the source to
+    // mirror against.
+    pub(crate) fn mirrors(&self) -> bool {
+        self.delete_extraneous && !self.flatten && self.path_transform.is_none()
+    }
+
+    // Like rsync, `src/` stands for the contents of `src` rather than the
+    // directory itself. Only directories have contents: a file is always
+    // copied as itself.
+    pub(crate) fn copies_contents(&self, src: &Path) -> bool {
+        let contents = match self.dest_mode {
+            DestMode::Auto => self.content_only || src.as_os_str().as_encoded_bytes().ends_with(b"/"),
+            DestMode::As => true,
+            DestMode::Into => false,
+        };
+        contents && src.is_dir()
+    }
+
+    // Whether `src` is copied to `dst/<name of src>` rather than to `dst`.
+    pub(crate) fn lands_inside(&self, src: &Path, dst: &Path) -> bool {
+        match self.dest_mode {
+            DestMode::Auto => dst.is_dir() && !self.copies_contents(src),
+            DestMode::As => false,
+            DestMode::Into => true,
+        }
+    }
+
+    // Builds with the `no-follow-symlinks` feature never dereference a link
+    // in the source, whatever the options say, not even `src` itself.
+    pub(crate) fn follows_symlinks(&self) -> bool {
+        cfg!(not(feature = "no-follow-symlinks")) && self.follow_symlinks
+    }
+
+    pub(crate) fn follows_top_level_symlink(&self) -> bool {
+        cfg!(not(feature = "no-follow-symlinks")) && self.follow_top_level_symlink
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), CopyError> {
+        if self.cancel.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+            return Err(CopyError::Cancelled);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(CopyError::TimedOut);
         }
+        Ok(())
+    }
+
+    pub(crate) fn resolved_overwrite(&self) -> OverwriteMode {
+        match self.overwrite_mode {
+            OverwriteMode::Never if self.overwrite => OverwriteMode::Always,
+            mode => mode,
+        }
+    }
+
+    /// Starts a [`CopyOptionsBuilder`] from the default options.
+    ///
+    /// ```
+    /// use recursive_copy::CopyOptions;
+    ///
+    /// let opts = CopyOptions::builder()
+    ///     .overwrite(true)
+    ///     .follow_symlinks(true)
+    ///     .restrict_symlinks(true)
+    ///     .exclude("*.tmp")
+    ///     .buffer_size(1 << 20)
+    ///     .build();
+    ///
+    /// assert!(opts.overwrite);
+    /// assert_eq!(opts.buffer_size, 1 << 20);
+    /// ```
+    pub fn builder() -> CopyOptionsBuilder {
+        CopyOptionsBuilder::default()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CopyOptionsBuilder {
+    opts: CopyOptions,
+}
+
+impl CopyOptionsBuilder {
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.opts.overwrite = overwrite;
+        self
+    }
+
+    pub fn overwrite_mode(mut self, mode: OverwriteMode) -> Self {
+        self.opts.overwrite_mode = mode;
+        self
+    }
+
+    pub fn overwrite_in_place(mut self, in_place: bool) -> Self {
+        self.opts.overwrite_in_place = in_place;
+        self
+    }
+
+    pub fn backup(mut self, mode: BackupMode) -> Self {
+        self.opts.backup = mode;
+        self
+    }
+
+    pub fn replace_dest(mut self, replace: bool) -> Self {
+        self.opts.replace_dest = replace;
+        self
+    }
+
+    pub fn detect_case_collisions(mut self, detect: bool) -> Self {
+        self.opts.detect_case_collisions = detect;
+        self
+    }
+
+    pub fn on_conflict<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path, &Path) -> Conflict + Send + Sync + 'static,
+    {
+        self.opts.on_conflict = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn restrict_symlinks(mut self, restrict: bool) -> Self {
+        self.opts.restrict_symlinks = restrict;
+        self
+    }
+
+    pub fn rewrite_symlink_targets(mut self, rewrite: bool) -> Self {
+        self.opts.rewrite_symlink_targets = rewrite;
+        self
+    }
+
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.opts.follow_symlinks = follow;
+        self
+    }
+
+    pub fn follow_top_level_symlink(mut self, follow: bool) -> Self {
+        self.opts.follow_top_level_symlink = follow;
+        self
+    }
+
+    pub fn follow_dst_symlink(mut self, follow: bool) -> Self {
+        self.opts.follow_dst_symlink = follow;
+        self
+    }
+
+    pub fn create_parents(mut self, create: bool) -> Self {
+        self.opts.create_parents = create;
+        self
+    }
+
+    pub fn copy_dangling_symlinks(mut self, copy: bool) -> Self {
+        self.opts.copy_dangling_symlinks = copy;
+        self
+    }
+
+    pub fn content_only(mut self, content_only: bool) -> Self {
+        self.opts.content_only = content_only;
+        self
+    }
+
+    pub fn dest_mode(mut self, mode: DestMode) -> Self {
+        self.opts.dest_mode = mode;
+        self
+    }
+
+    pub fn flatten(mut self, flatten: bool) -> Self {
+        self.opts.flatten = flatten;
+        self
+    }
+
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.opts.one_file_system = one_file_system;
+        self
+    }
+
+    pub fn preserve_timestamps(mut self, preserve: bool) -> Self {
+        self.opts.preserve_timestamps = preserve;
+        self
+    }
+
+    pub fn preserve_owner(mut self, preserve: bool) -> Self {
+        self.opts.preserve_owner = preserve;
+        self
+    }
+
+    pub fn uid_map(mut self, map: HashMap<u32, u32>) -> Self {
+        self.opts.uid_map = map;
+        self
+    }
+
+    pub fn gid_map(mut self, map: HashMap<u32, u32>) -> Self {
+        self.opts.gid_map = map;
+        self
+    }
+
+    pub fn strict_id_map(mut self, strict: bool) -> Self {
+        self.opts.strict_id_map = strict;
+        self
+    }
+
+    pub fn preserve_mode(mut self, preserve: bool) -> Self {
+        self.opts.preserve_mode = preserve;
+        self
+    }
+
+    pub fn preserve_xattr(mut self, preserve: bool) -> Self {
+        self.opts.preserve_xattr = preserve;
+        self
+    }
+
+    pub fn preserve_acl(mut self, preserve: bool) -> Self {
+        self.opts.preserve_acl = preserve;
+        self
+    }
+
+    pub fn preserve_flags(mut self, preserve: bool) -> Self {
+        self.opts.preserve_flags = preserve;
+        self
+    }
+
+    pub fn preserve_hardlinks(mut self, preserve: bool) -> Self {
+        self.opts.preserve_hardlinks = preserve;
+        self
+    }
+
+    pub fn reflink(mut self, mode: ReflinkMode) -> Self {
+        self.opts.reflink = mode;
+        self
+    }
+
+    pub fn copy_file_range(mut self, enable: bool) -> Self {
+        self.opts.copy_file_range = enable;
+        self
+    }
+
+    pub fn skip_if_reflinked(mut self, skip: bool) -> Self {
+        self.opts.skip_if_reflinked = skip;
+        self
+    }
+
+    pub fn link_instead_of_copy(mut self, link: bool) -> Self {
+        self.opts.link_instead_of_copy = link;
+        self
+    }
+
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.opts.dedup = dedup;
+        self
+    }
+
+    pub fn metadata_only(mut self, metadata_only: bool) -> Self {
+        self.opts.metadata_only = metadata_only;
+        self
+    }
+
+    pub fn sparse(mut self, mode: SparseMode) -> Self {
+        self.opts.sparse = mode;
+        self
+    }
+
+    pub fn preallocate(mut self, preallocate: bool) -> Self {
+        self.opts.preallocate = preallocate;
+        self
+    }
+
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.opts.include.push(pattern.into());
+        self
+    }
+
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.opts.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.opts.skip_hidden = skip;
+        self
+    }
+
+    pub fn respect_ignore_files(mut self, respect: bool) -> Self {
+        self.opts.respect_ignore_files = respect;
+        self
+    }
+
+    pub fn path_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&Path) -> Option<PathBuf> + Send + Sync + 'static,
+    {
+        self.opts.path_transform = Some(Arc::new(transform));
+        self
+    }
+
+    pub fn on_dir<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path, &Path) -> DirAction + Send + Sync + 'static,
+    {
+        self.opts.on_dir = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn delete_extraneous(mut self, delete: bool) -> Self {
+        self.opts.delete_extraneous = delete;
+        self
+    }
+
+    pub fn prune_empty_dirs(mut self, prune: bool) -> Self {
+        self.opts.prune_empty_dirs = prune;
+        self
+    }
+
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.opts.max_file_size = Some(size);
+        self
+    }
+
+    pub fn min_file_size(mut self, size: u64) -> Self {
+        self.opts.min_file_size = Some(size);
+        self
+    }
+
+    pub fn newer_than(mut self, time: SystemTime) -> Self {
+        self.opts.newer_than = Some(time);
+        self
+    }
+
+    pub fn max_total_bytes(mut self, max: u64) -> Self {
+        self.opts.max_total_bytes = Some(max);
+        self
+    }
+
+    pub fn max_files(mut self, max: usize) -> Self {
+        self.opts.max_files = Some(max);
+        self
+    }
+
+    pub fn copy_special(mut self, copy_special: bool) -> Self {
+        self.opts.copy_special = copy_special;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.opts.strict = strict;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.opts.dry_run = dry_run;
+        self
+    }
+
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.opts.atomic = atomic;
+        self
+    }
+
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.opts.resume = resume;
+        self
+    }
+
+    pub fn trust_partial(mut self, trust: bool) -> Self {
+        self.opts.trust_partial = trust;
+        self
+    }
+
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.opts.fsync = fsync;
+        self
+    }
+
+    pub fn verify(mut self, mode: VerifyMode) -> Self {
+        self.opts.verify = mode;
+        self
+    }
+
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.opts.continue_on_error = continue_on_error;
+        self
+    }
+
+    pub fn skip_unreadable(mut self, skip: bool) -> Self {
+        self.opts.skip_unreadable = skip;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.opts.retries = retries;
+        self
+    }
+
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.opts.retry_delay = delay;
+        self
+    }
+
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ProgressEvent) + Send + Sync + 'static,
+    {
+        self.opts.progress = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.opts.cancel = Some(flag);
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.opts.deadline = Some(deadline);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.opts.threads = threads;
+        self
+    }
+
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.opts.buffer_size = size;
+        self
+    }
+
+    pub fn max_bytes_per_sec(mut self, rate: u64) -> Self {
+        self.opts.max_bytes_per_sec = Some(rate);
+        self
+    }
+
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.opts.depth = depth;
+        self
+    }
+
+    pub fn min_depth(mut self, min_depth: usize) -> Self {
+        self.opts.min_depth = min_depth;
+        self
+    }
+
+    pub fn sort_by_name(mut self, sort: bool) -> Self {
+        self.opts.sort_by_name = sort;
+        self
+    }
+
+    pub fn max_recursion_depth(mut self, max: usize) -> Self {
+        self.opts.max_recursion_depth = Some(max);
+        self
+    }
+
+    pub fn fail_on_depth(mut self, fail: bool) -> Self {
+        self.opts.fail_on_depth = fail;
+        self
+    }
+
+    pub fn build(self) -> CopyOptions {
+        self.opts
+    }
+}
+
+impl fmt::Debug for CopyOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopyOptions")
+            .field("overwrite", &self.overwrite)
+            .field("overwrite_mode", &self.overwrite_mode)
+            .field("overwrite_in_place", &self.overwrite_in_place)
+            .field("backup", &self.backup)
+            .field("replace_dest", &self.replace_dest)
+            .field("detect_case_collisions", &self.detect_case_collisions)
+            .field("on_conflict", &self.on_conflict.as_ref().map(|_| "Fn(&Path, &Path) -> Conflict"))
+            .field("restrict_symlinks", &self.restrict_symlinks)
+            .field("rewrite_symlink_targets", &self.rewrite_symlink_targets)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("follow_top_level_symlink", &self.follow_top_level_symlink)
+            .field("follow_dst_symlink", &self.follow_dst_symlink)
+            .field("create_parents", &self.create_parents)
+            .field("copy_dangling_symlinks", &self.copy_dangling_symlinks)
+            .field("content_only", &self.content_only)
+            .field("dest_mode", &self.dest_mode)
+            .field("flatten", &self.flatten)
+            .field("one_file_system", &self.one_file_system)
+            .field("preserve_timestamps", &self.preserve_timestamps)
+            .field("preserve_owner", &self.preserve_owner)
+            .field("uid_map", &self.uid_map)
+            .field("gid_map", &self.gid_map)
+            .field("strict_id_map", &self.strict_id_map)
+            .field("preserve_mode", &self.preserve_mode)
+            .field("preserve_xattr", &self.preserve_xattr)
+            .field("preserve_acl", &self.preserve_acl)
+            .field("preserve_flags", &self.preserve_flags)
+            .field("preserve_hardlinks", &self.preserve_hardlinks)
+            .field("reflink", &self.reflink)
+            .field("copy_file_range", &self.copy_file_range)
+            .field("skip_if_reflinked", &self.skip_if_reflinked)
+            .field("link_instead_of_copy", &self.link_instead_of_copy)
+            .field("dedup", &self.dedup)
+            .field("metadata_only", &self.metadata_only)
+            .field("sparse", &self.sparse)
+            .field("preallocate", &self.preallocate)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("skip_hidden", &self.skip_hidden)
+            .field("respect_ignore_files", &self.respect_ignore_files)
+            .field("path_transform", &self.path_transform.as_ref().map(|_| "Fn(&Path) -> Option<PathBuf>"))
+            .field("on_dir", &self.on_dir.as_ref().map(|_| "Fn(&Path, &Path) -> DirAction"))
+            .field("delete_extraneous", &self.delete_extraneous)
+            .field("prune_empty_dirs", &self.prune_empty_dirs)
+            .field("max_file_size", &self.max_file_size)
+            .field("min_file_size", &self.min_file_size)
+            .field("newer_than", &self.newer_than)
+            .field("max_total_bytes", &self.max_total_bytes)
+            .field("max_files", &self.max_files)
+            .field("copy_special", &self.copy_special)
+            .field("strict", &self.strict)
+            .field("dry_run", &self.dry_run)
+            .field("atomic", &self.atomic)
+            .field("resume", &self.resume)
+            .field("trust_partial", &self.trust_partial)
+            .field("fsync", &self.fsync)
+            .field("verify", &self.verify)
+            .field("continue_on_error", &self.continue_on_error)
+            .field("skip_unreadable", &self.skip_unreadable)
+            .field("retries", &self.retries)
+            .field("retry_delay", &self.retry_delay)
+            .field("progress", &self.progress.as_ref().map(|_| "Fn(&ProgressEvent)"))
+            .field("cancel", &self.cancel)
+            .field("deadline", &self.deadline)
+            .field("threads", &self.threads)
+            .field("buffer_size", &self.buffer_size)
+            .field("max_bytes_per_sec", &self.max_bytes_per_sec)
+            .field("depth", &self.depth)
+            .field("min_depth", &self.min_depth)
+            .field("sort_by_name", &self.sort_by_name)
+            .field("max_recursion_depth", &self.max_recursion_depth)
+            .field("fail_on_depth", &self.fail_on_depth)
+            .finish()
     }
 }
@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use crate::{CopyError, CopyOptions, copy_tree};
+
+#[derive(Debug)]
+pub enum CopyOp {
+    CreateDir {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    CopyFile {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    Symlink {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    HardLink {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    Special {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    SyncMetadata {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    Delete {
+        dst: PathBuf,
+    },
+    Skip {
+        src: PathBuf,
+        dst: PathBuf,
+        reason: SkipReason,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    Exists,
+    Size,
+    Unreadable,
+    OutsideSource,
+    Dangling,
+    Special,
+    SizeMismatch,
+    NotNewer,
+    Reflinked,
+}
+
+// The operations of the plan being built on this thread. A plan is a dry run
+// kept on the calling thread, so the copy code only has to report what it
+// would do wherever it already counts it.
+thread_local! {
+    static PLAN: RefCell<Option<Vec<Result<CopyOp, CopyError>>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn record(op: impl FnOnce() -> CopyOp) {
+    PLAN.with_borrow_mut(|plan| {
+        if let Some(ops) = plan {
+            ops.push(Ok(op()));
+        }
+    });
+}
+
+// Keeps a collected error in sequence with the operations around it; hands
+// it back when no plan is being built.
+pub(crate) fn record_error(e: CopyError) -> Option<CopyError> {
+    PLAN.with_borrow_mut(|plan| match plan {
+        Some(ops) => {
+            ops.push(Err(e));
+            None
+        }
+        None => Some(e),
+    })
+}
+
+pub fn plan(
+    src: &Path,
+    dst: &Path,
+    opts: &CopyOptions,
+) -> impl Iterator<Item = Result<CopyOp, CopyError>> + use<> {
+    let opts = CopyOptions {
+        dry_run: true,
+        threads: 1,
+        ..opts.clone()
+    };
+    // Callbacks may plan another copy; theirs must not end up in this one.
+    let outer = PLAN.replace(Some(Vec::new()));
+    let res = copy_tree(src, dst, &opts, false);
+    let mut ops = PLAN.replace(outer).unwrap_or_default();
+    match res {
+        Ok((stats, _)) => ops.extend(stats.errors.into_iter().map(|(_, e)| Err(e))),
+        Err(failure) => ops.push(Err(failure.error)),
+    }
+    ops.into_iter()
+}
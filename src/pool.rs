@@ -0,0 +1,90 @@
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::shared::Shared;
+use crate::{copy_one, CopyError, CopyOptions, CopyStats};
+
+pub(crate) struct FileJob {
+    pub(crate) src: PathBuf,
+    pub(crate) dst: PathBuf,
+}
+
+pub(crate) struct Pool {
+    sender: SyncSender<FileJob>,
+    failed: Arc<AtomicBool>,
+}
+
+impl Pool {
+    pub(crate) fn submit(&self, job: FileJob) {
+        if self.sender.send(job).is_err() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+// The stats of the workers come back even when the copy failed, covering the
+// files finished before it stopped.
+pub(crate) fn with_pool<R>(threads: usize, opts: &CopyOptions, shared: &Shared, body: impl FnOnce(Pool) -> Result<R, CopyError>
+) -> (Result<R, CopyError>, CopyStats) {
+    let failed = Arc::new(AtomicBool::new(false));
+    let first_error = Mutex::new(None);
+    let (sender, receiver) = mpsc::sync_channel(threads * 4);
+    let receiver = Mutex::new(receiver);
+    let mut stats = CopyStats::default();
+
+    let result = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| work(&receiver, opts, shared, &failed, &first_error)))
+            .collect();
+
+        let result = body(Pool { sender, failed: Arc::clone(&failed) });
+        if result.is_err() {
+            failed.store(true, Ordering::Relaxed);
+        }
+
+        for handle in handles {
+            match handle.join() {
+                Ok(worker_stats) => stats.merge(worker_stats),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        result
+    });
+
+    let result = result.and_then(|r| match first_error.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        Some(e) => Err(e),
+        None => Ok(r),
+    });
+    (result, stats)
+}
+
+fn work(jobs: &Mutex<Receiver<FileJob>>, opts: &CopyOptions, shared: &Shared, failed: &AtomicBool,
+    first_error: &Mutex<Option<CopyError>>
+) -> CopyStats {
+    let mut stats = CopyStats::default();
+    let mut buf = Vec::new();
+    loop {
+        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok(job) = job else { break };
+        if failed.load(Ordering::Relaxed) {
+            continue;
+        }
+        match copy_one(&job.src, &job.dst, opts, shared, &mut buf, &mut stats) {
+            Err(e) if opts.continue_on_error && !e.is_fatal() => stats.errors.push((job.src, e)),
+            Err(e) => {
+                failed.store(true, Ordering::Relaxed);
+                first_error.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+            }
+            Ok(()) => {}
+        }
+    }
+    stats
+}
@@ -0,0 +1,11 @@
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    FileStarted { path: &'a Path, size: u64 },
+    BytesCopied { path: &'a Path, so_far: u64, total: u64 },
+    FileFinished { path: &'a Path },
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
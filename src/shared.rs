@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::hash;
+use crate::options::HashAlgo;
+use crate::{CopyError, CopyOptions};
+
+type Copies = Vec<(PathBuf, Option<String>)>;
+
+// State shared by every file copied during one call, including the copies
+// running on worker threads.
+pub(crate) struct Shared {
+    throttle: Option<Throttle>,
+    budget: Option<Budget>,
+    // With `max_files`, the number of files still allowed to be written.
+    files_left: Option<AtomicUsize>,
+    // With `dedup`, the destinations written so far by key, each with its
+    // SHA-256 digest once a second file with that key needed it.
+    contents: Option<Mutex<HashMap<DedupKey, Copies>>>,
+}
+
+// What two files must share besides their contents to be copied as one hard
+// link, whose names all have the same mode, owner, and times: the size, and
+// the mode, owner, and (when it is preserved) mtime of the sources.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct DedupKey {
+    pub(crate) size: u64,
+    mode: u32,
+    owner: (u32, u32),
+    mtime: Option<(i64, i64)>,
+}
+
+impl DedupKey {
+    pub(crate) fn new(meta: &fs::Metadata, opts: &CopyOptions) -> Self {
+        DedupKey {
+            size: meta.len(),
+            mode: meta.mode() & 0o7777,
+            owner: (meta.uid(), meta.gid()),
+            mtime: opts.preserve_timestamps.then(|| (meta.mtime(), meta.mtime_nsec())),
+        }
+    }
+}
+
+impl Shared {
+    pub(crate) fn new(opts: &CopyOptions) -> Self {
+        Shared {
+            throttle: opts
+                .max_bytes_per_sec
+                .filter(|&rate| rate > 0)
+                .map(Throttle::new),
+            budget: opts.max_total_bytes.map(|max| Budget {
+                max,
+                used: AtomicU64::new(0),
+            }),
+            files_left: opts.max_files.map(AtomicUsize::new),
+            contents: opts.dedup.then(|| Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Accounts for a file of `bytes` about to be copied, refusing it when it
+    // would take the total past `max_total_bytes`.
+    pub(crate) fn charge(&self, path: &Path, bytes: u64) -> Result<(), CopyError> {
+        let Some(budget) = &self.budget else {
+            return Ok(());
+        };
+        budget
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|&total| total <= budget.max)
+            })
+            .map(|_| ())
+            .map_err(|_| CopyError::BudgetExceeded(path.to_path_buf()))
+    }
+
+    // Claims one of the `max_files` slots for a file about to be written.
+    pub(crate) fn take_file(&self) -> bool {
+        self.files_left.as_ref().is_none_or(|left| {
+            left.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok()
+        })
+    }
+
+    pub(crate) fn files_exhausted(&self) -> bool {
+        self.files_left.as_ref().is_some_and(|left| left.load(Ordering::Relaxed) == 0)
+    }
+
+    // Whether a file with `key` was copied before, the only case where
+    // hashing the next one can pay off.
+    pub(crate) fn copied_like(&self, key: &DedupKey) -> bool {
+        self.contents.as_ref().is_some_and(|contents| {
+            contents.lock().unwrap_or_else(|e| e.into_inner()).contains_key(key)
+        })
+    }
+
+    // The first destination with `key` and `digest`. Earlier copies not
+    // hashed yet are hashed now, outside the lock, and at most once.
+    pub(crate) fn copy_of(&self, key: &DedupKey, digest: &str, buf: &mut [u8]) -> Option<PathBuf> {
+        let contents = self.contents.as_ref()?;
+        let earlier = contents.lock().unwrap_or_else(|e| e.into_inner()).get(key)?.clone();
+        for (dst, known) in earlier {
+            let found = match known {
+                Some(found) => found,
+                // A first copy that is gone or unreadable cannot be linked to.
+                None => match hash::hash_file(&dst, &HashAlgo::Sha256, buf) {
+                    Ok(found) => {
+                        let mut contents = contents.lock().unwrap_or_else(|e| e.into_inner());
+                        let mut copies = contents.get_mut(key).into_iter().flatten();
+                        if let Some((_, known)) = copies.find(|(p, _)| *p == dst) {
+                            *known = Some(found.clone());
+                        }
+                        found
+                    }
+                    Err(_) => continue,
+                },
+            };
+            if found == digest {
+                return Some(dst);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn remember(&self, key: DedupKey, digest: Option<String>, dst: &Path) {
+        if let Some(contents) = &self.contents {
+            let mut contents = contents.lock().unwrap_or_else(|e| e.into_inner());
+            contents.entry(key).or_default().push((dst.to_path_buf(), digest));
+        }
+    }
+
+    pub(crate) fn throttle(&self, bytes: u64) {
+        if let Some(throttle) = &self.throttle {
+            throttle.take(bytes);
+        }
+    }
+}
+
+struct Budget {
+    max: u64,
+    used: AtomicU64,
+}
+
+// Token bucket refilled at `rate` bytes per second and holding at most one
+// second worth of tokens. Callers may overdraw it; they then sleep until the
+// debt has been paid back, which caps the aggregate rate across threads.
+struct Throttle {
+    rate: f64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl Throttle {
+    fn new(rate: u64) -> Self {
+        Throttle {
+            rate: rate as f64,
+            bucket: Mutex::new((0.0, Instant::now())),
+        }
+    }
+
+    fn take(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let (tokens, last) = &mut *bucket;
+            let now = Instant::now();
+            *tokens =
+                (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(self.rate);
+            *last = now;
+            *tokens -= bytes as f64;
+            if *tokens < 0.0 {
+                -*tokens / self.rate
+            } else {
+                0.0
+            }
+        };
+        if wait > 0.0 {
+            thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
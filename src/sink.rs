@@ -0,0 +1,76 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::shared::Shared;
+use crate::{Context, CopyError, CopyFailure, CopyOptions, CopyStats, walk_and_copy};
+
+pub trait CopySink {
+    fn on_dir(&mut self, path: &Path, meta: &fs::Metadata) -> io::Result<()>;
+
+    fn on_file(
+        &mut self,
+        path: &Path,
+        meta: &fs::Metadata,
+        contents: &mut dyn Read,
+    ) -> io::Result<()>;
+
+    fn on_symlink(&mut self, path: &Path, target: &Path, meta: &fs::Metadata) -> io::Result<()>;
+}
+
+pub fn copy_recursive_to_sink<S: CopySink>(
+    src: &Path,
+    sink: &mut S,
+    opts: &CopyOptions,
+) -> Result<CopyStats, CopyFailure> {
+    let failed = |error: CopyError| CopyFailure { stats: Box::default(), error };
+    if let Some(stats) = stream_single(src, sink, opts).map_err(failed)? {
+        return Ok(stats);
+    }
+
+    let root = if opts.copies_contents(src) {
+        PathBuf::new()
+    } else {
+        PathBuf::from(src.file_name().unwrap_or_default())
+    };
+    let shared = Shared::new(opts);
+    let mut ctx = Context::new(opts, &shared, PathBuf::new(), CopyStats::default());
+    ctx.sink = Some(sink);
+    let walked = walk_and_copy(src, &root, &mut ctx);
+    CopyFailure::with_stats(ctx.stats, walked)
+}
+
+// Streams a source that is not a directory straight to the sink; `None` means
+// it is a directory and has to be walked.
+fn stream_single<S: CopySink>(
+    src: &Path,
+    sink: &mut S,
+    opts: &CopyOptions,
+) -> Result<Option<CopyStats>, CopyError> {
+    let mut stats = CopyStats::default();
+    let name = PathBuf::from(src.file_name().unwrap_or_default());
+
+    if !opts.follows_top_level_symlink() && src.is_symlink() {
+        sink.on_symlink(&name, &fs::read_link(src)?, &fs::symlink_metadata(src)?)?;
+        stats.symlinks_created += 1;
+        return Ok(Some(stats));
+    }
+
+    if !src.exists() {
+        return Err(CopyError::SrcNotFound(src.to_path_buf()));
+    }
+    let meta = fs::metadata(src)?;
+
+    if meta.is_file() {
+        let mut file = fs::File::open(src)?;
+        sink.on_file(&name, &meta, &mut file)?;
+        stats.files_copied += 1;
+        stats.bytes_copied += meta.len();
+        return Ok(Some(stats));
+    }
+
+    if !meta.is_dir() {
+        return Err(CopyError::NotSupported(src.to_path_buf()));
+    }
+    Ok(None)
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use crate::CopyError;
+
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct CopyStats {
+    pub files_copied: u64,
+    pub dirs_created: u64,
+    pub symlinks_created: u64,
+    pub hardlinks_created: u64,
+    pub files_deduplicated: u64,
+    pub specials_created: u64,
+    pub skipped_special: Vec<PathBuf>,
+    pub skipped_symlinks: Vec<(PathBuf, PathBuf)>,
+    pub truncated_dirs: Vec<PathBuf>,
+    pub truncated: bool,
+    pub bytes_copied: u64,
+    pub files_skipped: u64,
+    pub metadata_synced: u64,
+    pub entries_deleted: u64,
+    pub owners_not_preserved: u64,
+    pub destination: PathBuf,
+    pub errors: Vec<(PathBuf, CopyError)>,
+}
+
+impl CopyStats {
+    pub(crate) fn merge(&mut self, other: CopyStats) {
+        self.files_copied += other.files_copied;
+        self.dirs_created += other.dirs_created;
+        self.symlinks_created += other.symlinks_created;
+        self.hardlinks_created += other.hardlinks_created;
+        self.files_deduplicated += other.files_deduplicated;
+        self.specials_created += other.specials_created;
+        self.skipped_special.extend(other.skipped_special);
+        self.skipped_symlinks.extend(other.skipped_symlinks);
+        self.truncated_dirs.extend(other.truncated_dirs);
+        self.truncated |= other.truncated;
+        self.bytes_copied += other.bytes_copied;
+        self.files_skipped += other.files_skipped;
+        self.metadata_synced += other.metadata_synced;
+        self.entries_deleted += other.entries_deleted;
+        self.owners_not_preserved += other.owners_not_preserved;
+        self.errors.extend(other.errors);
+    }
+}
@@ -0,0 +1,512 @@
+use std::ffi::{CStr, CString};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::raw::{c_char, c_int, c_long, c_uint, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+type IoctlRequest = std::os::raw::c_ulong;
+#[cfg(all(target_os = "linux", target_env = "musl"))]
+type IoctlRequest = c_int;
+
+#[cfg(all(target_os = "linux", any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+)))]
+const FICLONE: IoctlRequest = 0x80049409u32 as IoctlRequest;
+#[cfg(all(target_os = "linux", not(any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+))))]
+const FICLONE: IoctlRequest = 0x40049409;
+
+// `_IOWR('f', 11, struct fiemap)`, the same on every architecture.
+#[cfg(target_os = "linux")]
+const FS_IOC_FIEMAP: IoctlRequest = 0xC020660Bu32 as IoctlRequest;
+
+// Declared with a `long` argument, though the kernel reads and writes an `int`.
+#[cfg(target_os = "linux")]
+const FLAGS_ARG_SIZE: u32 = (std::mem::size_of::<c_long>() as u32) << 16;
+#[cfg(all(target_os = "linux", any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+)))]
+const FS_IOC_GETFLAGS: IoctlRequest = (0x40006601 | FLAGS_ARG_SIZE) as IoctlRequest;
+#[cfg(all(target_os = "linux", any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+)))]
+const FS_IOC_SETFLAGS: IoctlRequest = (0x80006602u32 | FLAGS_ARG_SIZE) as IoctlRequest;
+#[cfg(all(target_os = "linux", not(any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+))))]
+const FS_IOC_GETFLAGS: IoctlRequest = (0x80006601u32 | FLAGS_ARG_SIZE) as IoctlRequest;
+#[cfg(all(target_os = "linux", not(any(
+    target_arch = "powerpc", target_arch = "powerpc64",
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc64"
+))))]
+const FS_IOC_SETFLAGS: IoctlRequest = (0x40006602 | FLAGS_ARG_SIZE) as IoctlRequest;
+
+#[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "openbsd"))]
+type ModeT = u32;
+#[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly"))]
+type ModeT = u16;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+type DevT = u64;
+#[cfg(any(target_vendor = "apple", target_os = "openbsd"))]
+type DevT = i32;
+#[cfg(target_os = "dragonfly")]
+type DevT = u32;
+
+pub(crate) const S_IFMT: u32 = 0o170000;
+
+#[cfg(all(target_os = "linux", any(
+    target_arch = "arm", target_arch = "aarch64", target_arch = "powerpc", target_arch = "powerpc64"
+)))]
+const O_DIRECTORY: c_int = 0o40000;
+#[cfg(all(target_os = "linux", not(any(
+    target_arch = "arm", target_arch = "aarch64", target_arch = "powerpc", target_arch = "powerpc64"
+))))]
+const O_DIRECTORY: c_int = 0o200000;
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+const AT_FDCWD: c_int = -100;
+#[cfg(target_vendor = "apple")]
+const AT_FDCWD: c_int = -2;
+#[cfg(target_os = "dragonfly")]
+const AT_FDCWD: c_int = 0xFFFAFDCDu32 as c_int;
+#[cfg(target_os = "linux")]
+const AT_SYMLINK_NOFOLLOW: c_int = 0x100;
+#[cfg(target_vendor = "apple")]
+const AT_SYMLINK_NOFOLLOW: c_int = 0x20;
+#[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+const AT_SYMLINK_NOFOLLOW: c_int = 0x200;
+#[cfg(target_os = "openbsd")]
+const AT_SYMLINK_NOFOLLOW: c_int = 0x02;
+#[cfg(target_os = "dragonfly")]
+const AT_SYMLINK_NOFOLLOW: c_int = 0x01;
+
+// `time_t` is a `long` except on the target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"s that moved to 64 bits everywhere
+// (Freetarget_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd" keeps 32 bits on i386 only).
+#[cfg(any(target_os = "linux", target_vendor = "apple", all(target_os = "freebsd", target_arch = "x86")))]
+type TimeT = c_long;
+#[cfg(any(
+    all(target_os = "freebsd", not(target_arch = "x86")),
+    target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"
+))]
+type TimeT = i64;
+
+#[cfg(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+#[repr(C)]
+struct Timespec {
+    tv_sec: TimeT,
+    tv_nsec: c_long,
+}
+
+#[cfg(target_os = "linux")]
+const FIEMAP_BATCH: usize = 32;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct Fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+    fm_extents: [FiemapExtent; FIEMAP_BATCH],
+}
+
+#[cfg(target_os = "linux")]
+unsafe extern "C" {
+    fn ioctl(fd: c_int, request: IoctlRequest, ...) -> c_int;
+    fn fchmodat(dirfd: c_int, path: *const c_char, mode: ModeT, flags: c_int) -> c_int;
+    fn llistxattr(path: *const c_char, list: *mut c_char, size: usize) -> isize;
+    fn lgetxattr(path: *const c_char, name: *const c_char, value: *mut c_void, size: usize) -> isize;
+    fn lsetxattr(path: *const c_char, name: *const c_char, value: *const c_void, size: usize, flags: c_int
+    ) -> c_int;
+    #[cfg_attr(all(target_env = "gnu", target_pointer_width = "32"), link_name = "lseek64")]
+    fn lseek(fd: c_int, offset: i64, whence: c_int) -> i64;
+    #[cfg_attr(all(target_env = "gnu", target_pointer_width = "32"), link_name = "fallocate64")]
+    fn fallocate(fd: c_int, mode: c_int, offset: i64, len: i64) -> c_int;
+    fn copy_file_range(
+        fd_in: c_int, off_in: *mut i64, fd_out: c_int, off_out: *mut i64, len: usize, flags: c_uint,
+    ) -> isize;
+}
+
+#[cfg(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+unsafe extern "C" {
+    fn mkfifo(path: *const c_char, mode: ModeT) -> c_int;
+    #[cfg_attr(target_os = "netbsd", link_name = "__mknod50")]
+    fn mknod(path: *const c_char, mode: ModeT, dev: DevT) -> c_int;
+    fn utimensat(dirfd: c_int, path: *const c_char, times: *const Timespec, flags: c_int) -> c_int;
+}
+
+#[cfg(target_os = "linux")]
+const ERANGE: i32 = 34;
+#[cfg(target_os = "linux")]
+const ENXIO: i32 = 6;
+#[cfg(target_os = "linux")]
+const FALLOC_FL_KEEP_SIZE: c_int = 0x01;
+#[cfg(target_os = "linux")]
+const FIEMAP_FLAG_SYNC: u32 = 0x01;
+#[cfg(target_os = "linux")]
+const FIEMAP_EXTENT_LAST: u32 = 0x01;
+// Extents whose `fe_physical` does not tell where the data is: not allocated
+// yet, delayed, or stored inline in the metadata.
+#[cfg(target_os = "linux")]
+const FIEMAP_EXTENT_NO_LOCATION: u32 = 0x02 | 0x04 | 0x200;
+#[cfg(target_os = "linux")]
+const SEEK_SET: c_int = 0;
+#[cfg(target_os = "linux")]
+const SEEK_DATA: c_int = 3;
+#[cfg(target_os = "linux")]
+const SEEK_HOLE: c_int = 4;
+
+fn cstr(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+fn cvt(ret: c_int) -> io::Result<c_int> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn reflink(src: &File, dst: &File) -> io::Result<()> {
+    cvt(unsafe { ioctl(dst.as_raw_fd(), FICLONE, src.as_raw_fd()) })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn reflink(_src: &File, _dst: &File) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// The inode flags of the file or directory at `path`, as shown by `lsattr`.
+#[cfg(target_os = "linux")]
+pub(crate) fn inode_flags(path: &Path) -> io::Result<i32> {
+    let file = File::open(path)?;
+    let mut flags: c_int = 0;
+    cvt(unsafe { ioctl(file.as_raw_fd(), FS_IOC_GETFLAGS, &mut flags) })?;
+    Ok(flags)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn set_inode_flags(path: &Path, flags: i32) -> io::Result<()> {
+    let file = File::open(path)?;
+    cvt(unsafe { ioctl(file.as_raw_fd(), FS_IOC_SETFLAGS, &flags) })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn inode_flags(_path: &Path) -> io::Result<i32> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_inode_flags(_path: &Path, _flags: i32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// Copies up to `len` bytes at `offset` of `src` to the same offset of `dst`
+// inside the kernel, leaving both file positions alone.
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_range(src: &File, dst: &File, offset: u64, len: u64) -> io::Result<u64> {
+    let mut off_in = i64::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let mut off_out = off_in;
+    let len = usize::try_from(len).unwrap_or(usize::MAX);
+    let n = unsafe { copy_file_range(src.as_raw_fd(), &mut off_in, dst.as_raw_fd(), &mut off_out, len, 0) };
+    if n == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(n as u64)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn copy_range(_src: &File, _dst: &File, _offset: u64, _len: u64) -> io::Result<u64> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    let len = i64::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    // Keeping the size means a source that shrinks mid-copy does not leave
+    // a zero-filled tail behind.
+    cvt(unsafe { fallocate(file.as_raw_fd(), FALLOC_FL_KEEP_SIZE, 0, len) })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn preallocate(_file: &File, _len: u64) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn data_extents(file: &File, len: u64) -> io::Result<Vec<(u64, u64)>> {
+    let fd = file.as_raw_fd();
+    let seek = |offset: i64, whence: c_int| match unsafe { lseek(fd, offset, whence) } {
+        -1 => Err(io::Error::last_os_error()),
+        pos => Ok(pos),
+    };
+
+    let mut extents = Vec::new();
+    let mut pos = 0;
+    while (pos as u64) < len {
+        let start = match seek(pos, SEEK_DATA) {
+            Ok(start) => start,
+            Err(e) if e.raw_os_error() == Some(ENXIO) => break,
+            Err(e) => return Err(e),
+        };
+        let end = seek(start, SEEK_HOLE)?;
+        extents.push((start as u64, (end as u64).min(len)));
+        pos = end;
+    }
+    seek(0, SEEK_SET)?;
+    Ok(extents)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn data_extents(_file: &File, _len: u64) -> io::Result<Vec<(u64, u64)>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// The `(logical, physical, length)` extents of `file` as reported by
+// `FIEMAP`, after flushing its dirty pages. Files with data that has no
+// physical location yet are reported as unsupported.
+#[cfg(target_os = "linux")]
+pub(crate) fn physical_extents(file: &File) -> io::Result<Vec<(u64, u64, u64)>> {
+    let mut extents = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut map = Fiemap {
+            fm_start: start,
+            fm_length: u64::MAX - start,
+            fm_flags: FIEMAP_FLAG_SYNC,
+            fm_mapped_extents: 0,
+            fm_extent_count: FIEMAP_BATCH as u32,
+            fm_reserved: 0,
+            fm_extents: [FiemapExtent::default(); FIEMAP_BATCH],
+        };
+        cvt(unsafe { ioctl(file.as_raw_fd(), FS_IOC_FIEMAP, &mut map) })?;
+        let mapped = &map.fm_extents[..(map.fm_mapped_extents as usize).min(FIEMAP_BATCH)];
+        let Some(last) = mapped.last() else {
+            return Ok(extents);
+        };
+        for extent in mapped {
+            if extent.fe_flags & FIEMAP_EXTENT_NO_LOCATION != 0 {
+                return Err(io::Error::from(io::ErrorKind::Unsupported));
+            }
+            extents.push((extent.fe_logical, extent.fe_physical, extent.fe_length));
+        }
+        if last.fe_flags & FIEMAP_EXTENT_LAST != 0 {
+            return Ok(extents);
+        }
+        start = last.fe_logical + last.fe_length;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn physical_extents(_file: &File) -> io::Result<Vec<(u64, u64, u64)>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    OpenOptions::new().read(true).custom_flags(O_DIRECTORY).open(path)?.sync_all()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    OpenOptions::new().read(true).open(path)?.sync_all()
+}
+
+#[cfg(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+pub(crate) fn make_fifo(path: &Path, mode: u32) -> io::Result<()> {
+    let path = cstr(path)?;
+    cvt(unsafe { mkfifo(path.as_ptr(), mode as ModeT) })?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+pub(crate) fn make_node(path: &Path, mode: u32, dev: u64) -> io::Result<()> {
+    let path = cstr(path)?;
+    cvt(unsafe { mknod(path.as_ptr(), mode as ModeT, dev as DevT) })?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd")))]
+pub(crate) fn make_fifo(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd")))]
+pub(crate) fn make_node(_path: &Path, _mode: u32, _dev: u64) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+pub(crate) fn set_times_nofollow(path: &Path, meta: &std::fs::Metadata) -> io::Result<()> {
+    let path = cstr(path)?;
+    let times = [
+        Timespec { tv_sec: meta.atime() as TimeT, tv_nsec: meta.atime_nsec() as c_long },
+        Timespec { tv_sec: meta.mtime() as TimeT, tv_nsec: meta.mtime_nsec() as c_long },
+    ];
+    cvt(unsafe { utimensat(AT_FDCWD, path.as_ptr(), times.as_ptr(), AT_SYMLINK_NOFOLLOW) })?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd")))]
+pub(crate) fn set_times_nofollow(_path: &Path, _meta: &std::fs::Metadata) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// `lchmod`. Linux keeps symlinks at 0777 and fails with `EOPNOTSUPP`.
+#[cfg(target_os = "linux")]
+pub(crate) fn chmod_nofollow(path: &Path, mode: u32) -> io::Result<()> {
+    let path = cstr(path)?;
+    cvt(unsafe { fchmodat(AT_FDCWD, path.as_ptr(), mode, AT_SYMLINK_NOFOLLOW) })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn chmod_nofollow(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(target_os = "linux")]
+fn read_sized(mut call: impl FnMut(*mut c_void, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let len = call(std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; len as usize];
+        let got = call(buf.as_mut_ptr().cast(), buf.len());
+        if got >= 0 {
+            buf.truncate(got as usize);
+            return Ok(buf);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn list_xattrs(path: &Path) -> io::Result<Vec<CString>> {
+    let path = cstr(path)?;
+    let list = read_sized(|buf, size| unsafe { llistxattr(path.as_ptr(), buf.cast(), size) })?;
+    Ok(list
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| CString::new(name).ok())
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn get_xattr(path: &Path, name: &CStr) -> io::Result<Vec<u8>> {
+    let path = cstr(path)?;
+    read_sized(|buf, size| unsafe { lgetxattr(path.as_ptr(), name.as_ptr(), buf, size) })
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn set_xattr(path: &Path, name: &CStr, value: &[u8]) -> io::Result<()> {
+    let path = cstr(path)?;
+    cvt(unsafe { lsetxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn list_xattrs(_path: &Path) -> io::Result<Vec<CString>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn get_xattr(_path: &Path, _name: &CStr) -> io::Result<Vec<u8>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_xattr(_path: &Path, _name: &CStr, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+const ENOTTY: i32 = 25;
+
+pub(crate) const ACL_ACCESS: &CStr = c"system.posix_acl_access";
+pub(crate) const ACL_DEFAULT: &CStr = c"system.posix_acl_default";
+// mips and sparc keep the errno numbering of the systems Linux was first
+// ported from there, and the BSDs have their own.
+#[cfg(all(not(feature = "no-follow-symlinks"), target_os = "linux", any(
+    target_arch = "mips", target_arch = "mips64"
+)))]
+pub(crate) const ELOOP: i32 = 90;
+#[cfg(all(not(feature = "no-follow-symlinks"), target_os = "linux", any(
+    target_arch = "sparc", target_arch = "sparc64"
+)))]
+pub(crate) const ELOOP: i32 = 62;
+#[cfg(all(not(feature = "no-follow-symlinks"), target_os = "linux", not(any(
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc", target_arch = "sparc64"
+))))]
+pub(crate) const ELOOP: i32 = 40;
+#[cfg(all(not(feature = "no-follow-symlinks"), not(target_os = "linux")))]
+pub(crate) const ELOOP: i32 = 62;
+#[cfg(all(target_os = "linux", any(target_arch = "mips", target_arch = "mips64")))]
+const EOPNOTSUPP: i32 = 122;
+#[cfg(all(target_os = "linux", any(target_arch = "sparc", target_arch = "sparc64")))]
+const EOPNOTSUPP: i32 = 45;
+#[cfg(all(target_os = "linux", not(any(
+    target_arch = "mips", target_arch = "mips64", target_arch = "sparc", target_arch = "sparc64"
+))))]
+const EOPNOTSUPP: i32 = 95;
+#[cfg(target_vendor = "apple")]
+const EOPNOTSUPP: i32 = 102;
+#[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+const EOPNOTSUPP: i32 = 45;
+// mips happens to agree with the generic value here. macOS and the BSDs
+// report a missing attribute as `ENOATTR`.
+#[cfg(all(target_os = "linux", any(target_arch = "sparc", target_arch = "sparc64")))]
+pub(crate) const ENODATA: i32 = 111;
+#[cfg(all(target_os = "linux", not(any(target_arch = "sparc", target_arch = "sparc64"))))]
+pub(crate) const ENODATA: i32 = 61;
+#[cfg(target_vendor = "apple")]
+pub(crate) const ENODATA: i32 = 93;
+#[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+pub(crate) const ENODATA: i32 = 87;
+
+pub(crate) const FS_IMMUTABLE_FL: i32 = 0x10;
+pub(crate) const FS_APPEND_FL: i32 = 0x20;
+// The `chattr` flags that describe how a file is to be treated rather than
+// how it is stored: `S`, `i`, `a`, `d`, `A`, and `D`.
+pub(crate) const COPIED_FLAGS: i32 = 0x08 | FS_IMMUTABLE_FL | FS_APPEND_FL | 0x40 | 0x80 | 0x10000;
+
+pub(crate) fn is_unsupported(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Unsupported | io::ErrorKind::CrossesDevices | io::ErrorKind::InvalidInput
+    ) || matches!(e.raw_os_error(), Some(ENOTTY | EOPNOTSUPP))
+}
@@ -21,15 +21,39 @@ fn cleanup(path: &PathBuf) {
     }
 }
 
+// Scratch directory unique to this test process, removed when dropped so
+// concurrent `cargo test` runs neither collide nor leave files behind.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        cleanup(&path);
+        TestDir(path)
+    }
+}
+
+impl std::ops::Deref for TestDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        cleanup(&self.0);
+    }
+}
+
 
 #[test]
 fn test_copy_recursive_with_symlinks() {
-    let base = PathBuf::from("/tmp/recursive_copy_test_symlinks");
+    let base = TestDir::new("recursive_copy_test_symlinks");
     let src = base.join("src");
     let dst = base.join("dst");
 
-    cleanup(&base);
-
     let create = NO_DEST;
     if !create {
         fs::create_dir_all(&dst).unwrap();
@@ -45,9 +69,11 @@ fn test_copy_recursive_with_symlinks() {
     std::os::unix::fs::symlink("root.txt", &symlink_file).unwrap_or_default();
     std::os::unix::fs::symlink("subdir/nested", &symlink_dir).unwrap_or_default();
 
-    let mut opts = CopyOptions::default();
-    opts.follow_symlinks = SYMLINKS;
-    opts.content_only = COPY_ONLY;
+    let opts = CopyOptions {
+        follow_symlinks: SYMLINKS,
+        content_only: COPY_ONLY,
+        ..Default::default()
+    };
 
     let mut final_dst = PathBuf::from(&dst);
     if !opts.content_only && !create {
@@ -76,13 +102,11 @@ fn test_copy_recursive_with_symlinks() {
 
 #[test]
 fn test_copy_single_file_to_existing_dir() {
-    let base = PathBuf::from("/tmp/test_single_file");
+    let base = TestDir::new("test_single_file");
     let src_file = base.join("source_file.txt");
     let dst_dir = base.join("dest_dir");
     let expected_dst_file = dst_dir.join("source_file.txt");
 
-    cleanup(&base);
-
     fs::create_dir_all(&dst_dir).unwrap();
     create_file(&src_file, "This is the content of the file.");
 
@@ -100,4 +124,2912 @@ fn test_copy_single_file_to_existing_dir() {
 
     assert!(new_file_name.exists(), "The file should have been copied and renamed.");
     println!("  [OK] File copied successfully with rename: {}", new_file_name.display());
-}
\ No newline at end of file
+}
+#[test]
+fn test_copy_file_with_zero_buffer_size() {
+    let base = TestDir::new("test_zero_buffer");
+    let src_file = base.join("source_file.txt");
+    let dst_file = base.join("dest_file.txt");
+
+    create_file(&src_file, &"x".repeat(100_000));
+
+    let opts = CopyOptions {
+        buffer_size: 0,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Zero Buffer Size ---");
+    copy_recursive(&src_file, &dst_file, &opts).expect("File copy failed");
+
+    assert_eq!(fs::read(&src_file).unwrap(), fs::read(&dst_file).unwrap());
+    println!("  [OK] File copied with fallback buffer: {}", dst_file.display());
+
+    let huge = CopyOptions { buffer_size: usize::MAX, overwrite: true, ..Default::default() };
+    copy_recursive(&src_file, &dst_file, &huge).expect("File copy with huge buffer failed");
+    assert_eq!(buffer_len(&huge), MAX_BUFFER_SIZE);
+    assert_eq!(fs::read(&src_file).unwrap(), fs::read(&dst_file).unwrap());
+    println!("  [OK] Oversized buffer clamped to {} bytes", MAX_BUFFER_SIZE);
+}
+
+#[test]
+fn test_copy_stats_counts() {
+    let base = TestDir::new("recursive_copy_test_stats");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("a.txt"), "aaaa");
+    create_file(&src.join("sub/b.txt"), "bb");
+    std::os::unix::fs::symlink("a.txt", src.join("link_to_a")).unwrap();
+
+    let opts = CopyOptions::default();
+
+    println!("--- Running Test: Copy Stats ---");
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    assert_eq!(stats.files_copied, 2);
+    assert_eq!(stats.dirs_created, 2);
+    assert_eq!(stats.symlinks_created, 1);
+    assert_eq!(stats.bytes_copied, 8);
+    assert_eq!(stats.files_skipped, 0);
+
+    let merge = CopyOptions {
+        content_only: true,
+        ..Default::default()
+    };
+    let again = copy_recursive(&src, &dst, &merge).expect("Second copy failed");
+    assert_eq!(again.files_copied, 0);
+    assert_eq!(again.files_skipped, 3);
+    println!("  [OK] Stats: {:?}", stats);
+}
+
+#[test]
+fn test_copy_error_display_and_source() {
+    use std::error::Error;
+
+    let missing = PathBuf::from("/tmp/recursive_copy_test_missing_src");
+    cleanup(&missing);
+
+    println!("--- Running Test: Error Display ---");
+    let err = copy_recursive(&missing, Path::new("/tmp/unused_dst"), &CopyOptions::default())
+        .expect_err("Copy of missing source must fail").error;
+
+    assert_eq!(err.to_string(), format!("source path not found: {}", missing.display()));
+    assert!(err.source().is_none());
+
+    let io_err = CopyError::from(std::io::Error::other("boom"));
+    assert!(io_err.source().is_some());
+
+    let walk_err = CopyError::from(walkdir_minimal::WalkError::LoopDetected(missing.clone()));
+    assert!(matches!(walk_err, CopyError::Walk(_)));
+    assert!(walk_err.source().is_some());
+    println!("  [OK] {}", err);
+}
+
+#[test]
+fn test_preserve_timestamps() {
+    use std::time::{Duration, SystemTime};
+
+    let base = TestDir::new("recursive_copy_test_timestamps");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("sub/file.txt"), "timestamps");
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    let times = fs::FileTimes::new().set_accessed(old).set_modified(old);
+    File::open(src.join("sub/file.txt")).unwrap().set_times(times).unwrap();
+    File::open(src.join("sub")).unwrap().set_times(times).unwrap();
+
+    let opts = CopyOptions {
+        preserve_timestamps: true,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Preserve Timestamps ---");
+    copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    let file_mtime = fs::metadata(dst.join("sub/file.txt")).unwrap().modified().unwrap();
+    let dir_mtime = fs::metadata(dst.join("sub")).unwrap().modified().unwrap();
+    assert_eq!(file_mtime, old);
+    assert_eq!(dir_mtime, old);
+    println!("  [OK] Timestamps preserved under {}", dst.display());
+}
+
+#[test]
+fn test_reflink_auto_falls_back() {
+    let base = TestDir::new("recursive_copy_test_reflink");
+    let src_file = base.join("source.bin");
+    let dst_file = base.join("dest.bin");
+
+    create_file(&src_file, &"reflink ".repeat(10_000));
+
+    let opts = CopyOptions {
+        reflink: ReflinkMode::Auto,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Reflink Auto ---");
+    let stats = copy_recursive(&src_file, &dst_file, &opts).expect("File copy failed");
+
+    assert_eq!(fs::read(&src_file).unwrap(), fs::read(&dst_file).unwrap());
+    assert_eq!(stats.bytes_copied, fs::metadata(&src_file).unwrap().len());
+    println!("  [OK] Reflink or fallback copy succeeded: {}", dst_file.display());
+}
+
+#[test]
+fn test_skip_if_reflinked() {
+    let base = TestDir::new("recursive_copy_test_skip_reflinked");
+    let src_file = base.join("source.bin");
+    let dst_file = base.join("dest.bin");
+    create_file(&src_file, &"shared ".repeat(10_000));
+
+    println!("--- Running Test: Skip If Reflinked ---");
+    let opts = CopyOptions { overwrite: true, skip_if_reflinked: true, ..Default::default() };
+    copy_recursive(&src_file, &dst_file, &opts).unwrap();
+    let stats = copy_recursive(&src_file, &dst_file, &opts).unwrap();
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 0));
+    match sys::physical_extents(&File::open(&dst_file).unwrap()) {
+        Ok(extents) => assert_ne!(extents, sys::physical_extents(&File::open(&src_file).unwrap()).unwrap()),
+        Err(e) => assert!(sys::is_unsupported(&e)),
+    }
+    println!("  [OK] Independent copy rewritten");
+
+    // Only filesystems with reflinks (Btrfs, XFS) can produce a clone.
+    let clone = CopyOptions { reflink: ReflinkMode::Always, ..Default::default() };
+    if copy_recursive(&src_file, &base.join("clone.bin"), &clone).is_ok() {
+        let stats = copy_recursive(&src_file, &base.join("clone.bin"), &opts).unwrap();
+        assert_eq!((stats.files_copied, stats.files_skipped), (0, 1));
+        println!("  [OK] Clone left alone");
+    }
+}
+
+#[test]
+fn test_skip_hidden() {
+    let base = TestDir::new("recursive_copy_test_skip_hidden");
+    let src = base.join(".dotfiles");
+    create_file(&src.join("shown.txt"), "shown");
+    create_file(&src.join(".env"), "secret");
+    create_file(&src.join(".git/objects/ab/cdef"), "object");
+    create_file(&src.join("sub/.cache/blob"), "blob");
+    create_file(&src.join("sub/app.log"), "log");
+    create_file(&src.join("sub/app.rs"), "app");
+
+    println!("--- Running Test: Skip Hidden ---");
+    let opts = CopyOptions { skip_hidden: true, exclude: vec!["*.log".into()], ..Default::default() };
+    let dst = base.join("dst");
+    fs::create_dir_all(&dst).unwrap();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    let copied = dst.join(".dotfiles");
+    assert!(copied.join("shown.txt").exists() && copied.join("sub/app.rs").exists());
+    assert!(!copied.join(".env").exists());
+    assert!(!copied.join(".git").exists());
+    assert!(!copied.join("sub/.cache").exists());
+    assert!(!copied.join("sub/app.log").exists());
+    assert_eq!((stats.files_copied, stats.dirs_created), (2, 2));
+    println!("  [OK] Hidden entries pruned: {:?}", stats);
+}
+
+#[test]
+fn test_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    let name = |bytes: &[u8]| OsStr::from_bytes(bytes).to_os_string();
+    let names = |dir: &Path| {
+        let mut found: Vec<_> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        found.sort();
+        found
+    };
+
+    let base = TestDir::new("recursive_copy_test_non_utf8");
+    let src = base.join("src");
+    let sub = src.join(name(b"d\xff"));
+    create_file(&sub.join(name(b"caf\xe9.txt")), "latin-1");
+    create_file(&sub.join(name(b"caf\xe9.log")), "ignored");
+    create_file(&sub.join(name(b"x\xff.tmp")), "excluded");
+    symlink(name(b"caf\xe9.txt"), sub.join(name(b"l\xfe"))).unwrap();
+    fs::write(src.join(".copyignore"), b"caf\xe9.log\n").unwrap();
+
+    println!("--- Running Test: Non-UTF-8 Names ---");
+    let dst = base.join("dst");
+    let opts = CopyOptions { respect_ignore_files: true, exclude: vec!["*.tmp".into()], ..Default::default() };
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    let copied = dst.join(name(b"d\xff"));
+    assert_eq!(names(&copied), [name(b"caf\xe9.txt"), name(b"l\xfe")]);
+    assert_eq!(fs::read_to_string(copied.join(name(b"caf\xe9.txt"))).unwrap(), "latin-1\n");
+    assert_eq!(fs::read_link(copied.join(name(b"l\xfe"))).unwrap(), Path::new(&name(b"caf\xe9.txt")));
+    assert_eq!((stats.files_copied, stats.symlinks_created), (2, 1));
+    assert!(verify_tree(&src, &dst, &opts).unwrap().is_empty());
+    println!("  [OK] Names copied byte for byte, ignore file and exclude matched");
+
+    let opts = CopyOptions { overwrite: true, backup: BackupMode::Numbered, ..Default::default() };
+    let file = sub.join(name(b"caf\xe9.txt"));
+    copy_recursive(&file, &copied.join(name(b"caf\xe9.txt")), &opts).unwrap();
+    assert!(copied.join(name(b"caf\xe9.txt.~1~")).exists());
+    println!("  [OK] Numbered backup keeps the name");
+
+    create_file(&src.join(name(b"e\xff/caf\xe9.txt")), "other");
+    let flat = base.join("flat");
+    let opts = CopyOptions { flatten: true, content_only: true, ..Default::default() };
+    copy_recursive(&src, &flat, &opts).unwrap();
+    assert!(flat.join(name(b"caf\xe9.txt")).exists());
+    assert!(flat.join(name(b"caf\xe9 (1).txt")).exists());
+    println!("  [OK] Flattened duplicates renamed without losing bytes");
+}
+
+#[test]
+fn test_rewrite_symlink_targets() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_rewrite_links");
+    let src = base.join("src");
+    create_file(&src.join("sub/a.txt"), "a");
+    let root = src.canonicalize().unwrap();
+    symlink(root.join("sub/a.txt"), src.join("abs")).unwrap();
+    symlink(&root, src.join("sub/top")).unwrap();
+    symlink("/nonexistent/elsewhere", src.join("outside")).unwrap();
+    symlink("sub/a.txt", src.join("rel")).unwrap();
+
+    println!("--- Running Test: Rewrite Symlink Targets ---");
+    let dst = base.join("dst");
+    let opts = CopyOptions { rewrite_symlink_targets: true, ..Default::default() };
+    copy_recursive(&src, &dst, &opts).unwrap();
+    let copy = std::path::absolute(&dst).unwrap();
+    assert_eq!(fs::read_link(dst.join("abs")).unwrap(), copy.join("sub/a.txt"));
+    assert_eq!(fs::read_link(dst.join("sub/top")).unwrap(), copy);
+    assert_eq!(fs::read_link(dst.join("outside")).unwrap(), PathBuf::from("/nonexistent/elsewhere"));
+    assert_eq!(fs::read_link(dst.join("rel")).unwrap(), PathBuf::from("sub/a.txt"));
+    fs::remove_dir_all(&src).unwrap();
+    assert_eq!(fs::read_to_string(dst.join("abs")).unwrap(), "a\n");
+    println!("  [OK] Internal absolute links point into the copy");
+}
+
+#[test]
+fn test_include_exclude_filters() {
+    let base = TestDir::new("recursive_copy_test_filters");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("keep.rs"), "keep");
+    create_file(&src.join("notes.txt"), "notes");
+    create_file(&src.join("scratch.tmp"), "tmp");
+    create_file(&src.join("lib/mod.rs"), "mod");
+    create_file(&src.join("lib/cache.tmp"), "tmp");
+    create_file(&src.join("node_modules/pkg/index.rs"), "pkg");
+    create_file(&src.join(".git/config"), "git");
+
+    let opts = CopyOptions {
+        exclude: vec!["*.tmp".into(), "node_modules".into(), ".git/".into()],
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Exclude Filters ---");
+    copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    assert!(dst.join("keep.rs").exists());
+    assert!(dst.join("notes.txt").exists());
+    assert!(dst.join("lib/mod.rs").exists());
+    assert!(!dst.join("scratch.tmp").exists());
+    assert!(!dst.join("lib/cache.tmp").exists());
+    assert!(!dst.join("node_modules").exists());
+    assert!(!dst.join(".git").exists());
+
+    let included = base.join("included");
+    let opts = CopyOptions {
+        include: vec!["*.rs".into()],
+        exclude: vec!["/lib/**".into()],
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Include Filters ---");
+    copy_recursive(&src, &included, &opts).expect("Copy failed");
+
+    assert!(included.join("keep.rs").exists());
+    assert!(included.join("node_modules/pkg/index.rs").exists());
+    assert!(!included.join("notes.txt").exists());
+    assert!(!included.join("lib/mod.rs").exists());
+    println!("  [OK] Filters applied to {}", dst.display());
+}
+
+#[test]
+fn test_copy_special_fifo() {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let base = TestDir::new("recursive_copy_test_special");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    let skipped = base.join("skipped");
+
+    create_file(&src.join("regular.txt"), "regular");
+    sys::make_fifo(&src.join("pipe"), 0o640).unwrap();
+    fs::set_permissions(src.join("pipe"), fs::Permissions::from_mode(0o640)).unwrap();
+
+    println!("--- Running Test: Copy Special Files ---");
+    let stats = copy_recursive(&src, &skipped, &CopyOptions::default()).expect("Copy failed");
+    assert!(skipped.join("pipe").symlink_metadata().is_err());
+    assert_eq!(stats.skipped_special, vec![src.join("pipe")]);
+
+    let strict = CopyOptions { strict: true, ..Default::default() };
+    match copy_recursive(&src, &base.join("strict"), &strict).map_err(CopyError::from) {
+        Err(CopyError::NotSupported(path)) => assert_eq!(path, src.join("pipe")),
+        other => panic!("expected NotSupported, got {other:?}"),
+    }
+
+    let opts = CopyOptions {
+        copy_special: true,
+        strict: true,
+        preserve_timestamps: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    let meta = dst.join("pipe").symlink_metadata().unwrap();
+    assert!(meta.file_type().is_fifo());
+    assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+    assert_eq!(stats.specials_created, 1);
+    assert!(stats.skipped_special.is_empty());
+    println!("  [OK] FIFO recreated: {}", dst.join("pipe").display());
+}
+
+#[test]
+fn test_dry_run_touches_nothing() {
+    let base = TestDir::new("recursive_copy_test_dry_run");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("a.txt"), "aaaa");
+    create_file(&src.join("sub/b.txt"), "bb");
+    std::os::unix::fs::symlink("a.txt", src.join("link_to_a")).unwrap();
+
+    let opts = CopyOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Dry Run ---");
+    let planned = copy_recursive(&src, &dst, &opts).expect("Dry run failed");
+    assert!(!dst.exists(), "Dry run must not create the destination");
+
+    let actual = copy_recursive(&src, &dst, &CopyOptions::default()).expect("Copy failed");
+    assert_eq!(format!("{planned:?}"), format!("{actual:?}"));
+    println!("  [OK] Dry run stats match real copy: {:?}", planned);
+}
+
+#[test]
+fn test_progress_callback_events() {
+    use std::sync::{Arc, Mutex};
+
+    let base = TestDir::new("recursive_copy_test_progress");
+    let src_file = base.join("source.bin");
+    let dst_file = base.join("dest.bin");
+
+    create_file(&src_file, &"p".repeat(20_000));
+    let size = fs::metadata(&src_file).unwrap().len();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let opts = CopyOptions {
+        buffer_size: 8 * 1024,
+        progress: Some(Arc::new(move |e: &ProgressEvent| {
+            let label = match e {
+                ProgressEvent::FileStarted { size, .. } => format!("start {size}"),
+                ProgressEvent::BytesCopied { so_far, total, .. } => format!("bytes {so_far}/{total}"),
+                ProgressEvent::FileFinished { .. } => "finish".to_string(),
+            };
+            sink.lock().unwrap().push(label);
+        })),
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Progress Callback ---");
+    copy_recursive(&src_file, &dst_file, &opts).expect("File copy failed");
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.first().unwrap(), &format!("start {size}"));
+    assert_eq!(events[events.len() - 2], format!("bytes {size}/{size}"));
+    assert_eq!(events.last().unwrap(), "finish");
+    assert!(events.len() > 3, "Large file should report incremental progress");
+    println!("  [OK] {} progress events received", events.len());
+}
+
+#[test]
+fn test_reject_dest_inside_src() {
+    let base = TestDir::new("recursive_copy_test_inside");
+    let src = base.join("data");
+
+    create_file(&src.join("file.txt"), "data");
+
+    println!("--- Running Test: Destination Inside Source ---");
+    let err = copy_recursive(&src, &src.join("backup"), &CopyOptions::default())
+        .expect_err("Copy into itself must fail").error;
+
+    assert!(matches!(err, CopyError::DestInsideSrc(_)));
+    assert!(!src.join("backup").exists());
+    println!("  [OK] Rejected: {}", err);
+}
+
+#[test]
+fn test_overwrite_if_newer() {
+    use std::time::{Duration, SystemTime};
+
+    let base = TestDir::new("recursive_copy_test_if_newer");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("old.txt"), "new content");
+    create_file(&src.join("fresh.txt"), "new content");
+    create_file(&dst.join("old.txt"), "dst content");
+    create_file(&dst.join("fresh.txt"), "dst content");
+
+    let past = SystemTime::now() - Duration::from_secs(3600);
+    let times = fs::FileTimes::new().set_modified(past);
+    File::options().write(true).open(src.join("old.txt")).unwrap().set_times(times).unwrap();
+    File::options().write(true).open(dst.join("fresh.txt")).unwrap().set_times(times).unwrap();
+
+    let opts = CopyOptions {
+        overwrite_mode: OverwriteMode::IfNewer,
+        content_only: true,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Overwrite If Newer ---");
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    assert_eq!(fs::read_to_string(dst.join("old.txt")).unwrap(), "dst content\n");
+    assert_eq!(fs::read_to_string(dst.join("fresh.txt")).unwrap(), "new content\n");
+    assert_eq!(stats.files_copied, 1);
+    assert_eq!(stats.files_skipped, 1);
+    println!("  [OK] Only newer source replaced destination");
+}
+
+#[test]
+fn test_parallel_copy_matches_serial() {
+    let base = TestDir::new("recursive_copy_test_parallel");
+    let src = base.join("src");
+
+    for d in 0..4 {
+        for f in 0..25 {
+            create_file(&src.join(format!("dir{d}/file{f}.txt")), &format!("content {d} {f}"));
+        }
+    }
+
+    println!("--- Running Test: Parallel Copy ---");
+    let serial = copy_recursive(&src, &base.join("serial"), &CopyOptions::default()).expect("Serial copy failed");
+
+    let opts = CopyOptions {
+        threads: 4,
+        ..Default::default()
+    };
+    let parallel = copy_recursive(&src, &base.join("parallel"), &opts).expect("Parallel copy failed");
+
+    assert_eq!(parallel.destination, base.join("parallel"));
+    let parallel = CopyStats { destination: serial.destination.clone(), ..parallel };
+    assert_eq!(format!("{serial:?}"), format!("{parallel:?}"));
+    assert_eq!(parallel.files_copied, 100);
+    for d in 0..4 {
+        for f in 0..25 {
+            let rel = format!("dir{d}/file{f}.txt");
+            assert_eq!(fs::read(src.join(&rel)).unwrap(), fs::read(base.join("parallel").join(&rel)).unwrap());
+        }
+    }
+    println!("  [OK] Parallel stats: {:?}", parallel);
+}
+
+#[test]
+fn test_preserve_xattr() {
+    use std::ffi::CString;
+
+    let base = TestDir::new("recursive_copy_test_xattr");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("tagged.txt"), "xattr");
+
+    let name = CString::new("user.recursive_copy").unwrap();
+    if sys::set_xattr(&src.join("tagged.txt"), &name, b"kept").is_err() {
+        println!("  [SKIP] Filesystem does not support user xattrs");
+        return;
+    }
+
+    let opts = CopyOptions {
+        preserve_xattr: true,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Preserve Xattrs ---");
+    copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    assert_eq!(sys::get_xattr(&dst.join("tagged.txt"), &name).unwrap(), b"kept");
+    println!("  [OK] Xattr preserved on {}", dst.join("tagged.txt").display());
+}
+
+#[test]
+fn test_continue_on_error_collects_failures() {
+    let base = TestDir::new("recursive_copy_test_continue");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("a.txt"), "a");
+    create_file(&src.join("b.txt"), "b");
+    create_file(&src.join("c.txt"), "c");
+    fs::create_dir_all(dst.join("b.txt")).unwrap();
+
+    println!("--- Running Test: Continue On Error ---");
+    let merge = CopyOptions {
+        overwrite: true,
+        content_only: true,
+        ..Default::default()
+    };
+    assert!(copy_recursive(&src, &dst, &merge).is_err());
+
+    let opts = CopyOptions {
+        overwrite: true,
+        content_only: true,
+        continue_on_error: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy must not abort");
+
+    assert_eq!(stats.errors.len(), 1);
+    assert!(stats.errors[0].0.ends_with("b.txt"));
+    assert_eq!(stats.files_copied, 2);
+    assert!(dst.join("a.txt").is_file() && dst.join("c.txt").is_file());
+    println!("  [OK] Collected error: {}", stats.errors[0].1);
+}
+
+#[test]
+fn test_atomic_copy_replaces_without_leftovers() {
+    let base = TestDir::new("recursive_copy_test_atomic");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("data.txt"), "new");
+    create_file(&dst.join("data.txt"), "old");
+
+    let opts = CopyOptions {
+        atomic: true,
+        overwrite: true,
+        content_only: true,
+        ..Default::default()
+    };
+
+    println!("--- Running Test: Atomic Copy ---");
+    copy_recursive(&src, &dst, &opts).expect("Copy failed");
+
+    assert_eq!(fs::read_to_string(dst.join("data.txt")).unwrap(), "new\n");
+    let leftovers: Vec<_> = fs::read_dir(&dst).unwrap()
+        .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(".copytmp-"))
+        .collect();
+    assert!(leftovers.is_empty(), "Temporary files must not remain");
+    println!("  [OK] Atomic replace: {}", dst.join("data.txt").display());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_copy_matches_sync() {
+    let base = TestDir::new("recursive_copy_test_async");
+    let src = base.join("src");
+
+    for d in 0..3 {
+        for f in 0..10 {
+            create_file(&src.join(format!("dir{d}/file{f}.txt")), &format!("async {d} {f}"));
+        }
+    }
+    unix_fs::symlink("dir0/file0.txt", src.join("link")).unwrap();
+
+    println!("--- Running Test: Async Copy ---");
+    let opts = CopyOptions {
+        threads: 3,
+        ..Default::default()
+    };
+    let sync = copy_recursive(&src, &base.join("sync"), &opts).expect("Sync copy failed");
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let copied = runtime
+        .block_on(copy_recursive_async(&src, &base.join("async"), &opts))
+        .expect("Async copy failed");
+
+    assert_eq!(copied.destination, base.join("async"));
+    let copied = CopyStats { destination: sync.destination.clone(), ..copied };
+    assert_eq!(format!("{sync:?}"), format!("{copied:?}"));
+    assert_eq!(copied.files_copied, 30);
+    assert_eq!(fs::read_link(base.join("async/link")).unwrap(), PathBuf::from("dir0/file0.txt"));
+    for d in 0..3 {
+        for f in 0..10 {
+            let rel = format!("dir{d}/file{f}.txt");
+            assert_eq!(fs::read(src.join(&rel)).unwrap(), fs::read(base.join("async").join(&rel)).unwrap());
+        }
+    }
+
+    let missing = runtime.block_on(copy_recursive_async(&base.join("missing"), &base.join("out"), &opts));
+    assert!(matches!(missing, Err(CopyFailure { error: CopyError::SrcNotFound(_), .. })));
+    println!("  [OK] Async stats: {:?}", copied);
+}
+
+#[test]
+fn test_verify_modes() {
+    let base = TestDir::new("recursive_copy_test_verify");
+    let src = base.join("src");
+
+    create_file(&src.join("data.txt"), &"archive ".repeat(4096));
+
+    println!("--- Running Test: Verify ---");
+    let mut sha = hash::hasher(&HashAlgo::Sha256);
+    sha.update(b"abc");
+    assert_eq!(hash::finish(sha), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    let sha = hash::hasher(&HashAlgo::Sha256);
+    assert_eq!(hash::finish(sha), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    // Padding that spills into a second block.
+    let mut sha = hash::hasher(&HashAlgo::Sha256);
+    sha.update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+    assert_eq!(hash::finish(sha), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    let mut sha = hash::hasher(&HashAlgo::Sha256);
+    sha.update(&[b'a'; 1_000_000]);
+    assert_eq!(hash::finish(sha), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    let mut crc = hash::hasher(&HashAlgo::Crc32);
+    crc.update(b"123456789");
+    assert_eq!(hash::finish(crc), "cbf43926");
+
+    for verify in [VerifyMode::Size, VerifyMode::Checksum(HashAlgo::Crc32), VerifyMode::Checksum(HashAlgo::Sha256)] {
+        let opts = CopyOptions { verify: verify.clone(), ..Default::default() };
+        let dst = base.join(format!("{verify:?}"));
+        copy_recursive(&src, &dst, &opts).expect("Verified copy failed");
+        assert_eq!(fs::read(src.join("data.txt")).unwrap(), fs::read(dst.join("data.txt")).unwrap());
+    }
+
+    // Corrupt the destination behind the copier's back after the first chunk.
+    let corrupt = base.join("corrupt");
+    let target = corrupt.join("data.txt");
+    let opts = CopyOptions {
+        verify: VerifyMode::Checksum(HashAlgo::Sha256),
+        buffer_size: 8 * 1024,
+        progress: Some(Arc::new(move |event: &ProgressEvent| {
+            if let ProgressEvent::BytesCopied { so_far, .. } = event
+                && *so_far == 8 * 1024
+            {
+                fs::OpenOptions::new().write(true).open(&target).unwrap().write_all(b"XXXX").unwrap();
+            }
+        })),
+        ..Default::default()
+    };
+    let err = copy_recursive(&src, &corrupt, &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::VerifyFailed { ref path, .. } if path == &src.join("data.txt")));
+    println!("  [OK] {}", err);
+}
+
+#[test]
+fn test_custom_hasher() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Sums the bytes; too weak for real use, but enough to tell files apart.
+    struct Sum(u64);
+
+    impl Hasher for Sum {
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.iter().map(|&b| b as u64).sum::<u64>();
+        }
+
+        fn finalize(self: Box<Self>) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+    }
+
+    let base = TestDir::new("recursive_copy_test_custom_hasher");
+    let src = base.join("src");
+    create_file(&src.join("a.txt"), "alpha");
+    create_file(&src.join("b.txt"), "beta");
+
+    println!("--- Running Test: Custom Hasher ---");
+    let made = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&made);
+    let algo = HashAlgo::Custom(Arc::new(move || {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Box::new(Sum(0)) as Box<dyn Hasher>
+    }));
+    assert_eq!(algo, algo.clone());
+    assert_ne!(algo, HashAlgo::Custom(Arc::new(|| Box::new(Sum(0)))));
+    let opts = CopyOptions { verify: VerifyMode::Checksum(algo), ..Default::default() };
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+    assert_eq!(stats.files_copied, 2);
+    // One hasher for the source of each file and one re-reading its copy.
+    assert_eq!(made.load(Ordering::Relaxed), 4);
+    println!("  [OK] Checksum verified with the custom hasher");
+
+    fs::write(base.join("dst/b.txt"), "btea\n").unwrap();
+    assert!(verify_tree(&src, &base.join("dst"), &opts).unwrap().is_empty());
+    fs::write(base.join("dst/b.txt"), "bet\n\n").unwrap();
+    let mismatches = verify_tree(&src, &base.join("dst"), &opts).unwrap();
+    assert!(matches!(&mismatches[..], [Mismatch::Content { .. }]));
+    println!("  [OK] verify_tree uses it too, for better or worse");
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn test_blake3() {
+    println!("--- Running Test: BLAKE3 ---");
+    let digest = |data: &[u8], step: usize| {
+        let mut hasher = hash::hasher(&HashAlgo::Blake3);
+        for chunk in data.chunks(step.max(1)) {
+            hasher.update(chunk);
+        }
+        hash::finish(hasher)
+    };
+    assert_eq!(digest(b"", 1), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+    assert_eq!(digest(b"abc", 1), "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+
+    // The official test vectors, whose inputs repeat the bytes 0 to 250.
+    let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+    for (len, expected) in [
+        (0, "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"),
+        (1, "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"),
+        (1024, "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"),
+        (1025, "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"),
+        (2048, "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a"),
+        (2049, "5f4d72f40d7a5f82b15ca2b2e44b1de3c2ef86c426c95c1af0b6879522563030"),
+    ] {
+        assert_eq!(digest(&data[..len], len), expected, "{len} bytes");
+    }
+
+    // Inputs spanning several chunks hash the same however they are fed in.
+    for len in [1023, 1024, 1025, 2048, 3073, 10_000] {
+        let whole = digest(&data[..len], len);
+        assert_eq!(whole, digest(&data[..len], 1));
+        assert_eq!(whole, digest(&data[..len], 64));
+        assert_eq!(whole, digest(&data[..len], 1000));
+    }
+    println!("  [OK] Known digests and chunked input");
+}
+
+#[test]
+fn test_one_file_system() {
+    use std::process::Command;
+
+    let base = TestDir::new("recursive_copy_test_one_fs");
+    let src = base.join("src");
+    let mount = src.join("mnt");
+
+    create_file(&src.join("local.txt"), "local");
+    fs::create_dir_all(&mount).unwrap();
+
+    println!("--- Running Test: One File System ---");
+    let mounted = Command::new("mount").args(["-t", "tmpfs", "none"]).arg(&mount).status();
+    if !mounted.is_ok_and(|s| s.success()) {
+        println!("  [SKIP] Cannot mount a tmpfs here");
+        return;
+    }
+    create_file(&mount.join("nested/remote.txt"), "remote");
+
+    let opts = CopyOptions {
+        one_file_system: true,
+        ..Default::default()
+    };
+    let copied = copy_recursive(&src, &base.join("dst"), &opts);
+    Command::new("umount").arg(&mount).status().ok();
+
+    let stats = copied.expect("One-filesystem copy failed");
+    assert!(base.join("dst/local.txt").exists());
+    assert!(base.join("dst/mnt").is_dir());
+    assert_eq!(fs::read_dir(base.join("dst/mnt")).unwrap().count(), 0);
+    assert_eq!(stats.files_copied, 1);
+    println!("  [OK] Mount point created empty: {:?}", stats);
+}
+
+#[test]
+fn test_preserve_hardlinks() {
+    let base = TestDir::new("recursive_copy_test_hardlinks");
+    let src = base.join("src");
+
+    create_file(&src.join("a/original.txt"), "shared inode");
+    fs::create_dir_all(src.join("b")).unwrap();
+    fs::hard_link(src.join("a/original.txt"), src.join("b/link.txt")).unwrap();
+    create_file(&src.join("single.txt"), "alone");
+
+    println!("--- Running Test: Preserve Hardlinks ---");
+    for threads in [1, 4] {
+        let dst = base.join(format!("dst{threads}"));
+        let opts = CopyOptions {
+            preserve_hardlinks: true,
+            threads,
+            ..Default::default()
+        };
+        let stats = copy_recursive(&src, &dst, &opts).expect("Hardlink copy failed");
+
+        let first = fs::metadata(dst.join("a/original.txt")).unwrap();
+        let second = fs::metadata(dst.join("b/link.txt")).unwrap();
+        assert_eq!(first.ino(), second.ino());
+        assert_eq!(first.nlink(), 2);
+        assert_eq!(stats.files_copied, 2);
+        assert_eq!(stats.hardlinks_created, 1);
+        println!("  [OK] threads={}: {:?}", threads, stats);
+    }
+
+    let plain = copy_recursive(&src, &base.join("plain"), &CopyOptions::default()).unwrap();
+    assert_eq!(plain.files_copied, 3);
+    assert_ne!(
+        fs::metadata(base.join("plain/a/original.txt")).unwrap().ino(),
+        fs::metadata(base.join("plain/b/link.txt")).unwrap().ino()
+    );
+}
+
+#[test]
+fn test_copy_to_sink() {
+    use std::io::Read;
+
+    #[derive(Default)]
+    struct Recorder {
+        entries: Vec<String>,
+    }
+
+    impl CopySink for Recorder {
+        fn on_dir(&mut self, path: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+            self.entries.push(format!("dir {}", path.display()));
+            Ok(())
+        }
+
+        fn on_file(&mut self, path: &Path, _meta: &fs::Metadata, contents: &mut dyn Read) -> io::Result<()> {
+            let mut data = String::new();
+            contents.read_to_string(&mut data)?;
+            self.entries.push(format!("file {} {}", path.display(), data.trim_end()));
+            Ok(())
+        }
+
+        fn on_symlink(&mut self, path: &Path, target: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+            self.entries.push(format!("link {} -> {}", path.display(), target.display()));
+            Ok(())
+        }
+    }
+
+    let base = TestDir::new("recursive_copy_test_sink");
+    let src = base.join("tree");
+
+    create_file(&src.join("sub/inner.txt"), "inner");
+    unix_fs::symlink("sub/inner.txt", src.join("alias")).unwrap();
+
+    println!("--- Running Test: Copy To Sink ---");
+    let mut sink = Recorder::default();
+    let stats = copy_recursive_to_sink(&src, &mut sink, &CopyOptions::default()).expect("Sink copy failed");
+    sink.entries.sort();
+    assert_eq!(
+        sink.entries,
+        ["dir tree", "dir tree/sub", "file tree/sub/inner.txt inner", "link tree/alias -> sub/inner.txt"]
+    );
+    assert_eq!((stats.files_copied, stats.dirs_created, stats.symlinks_created), (1, 2, 1));
+
+    #[cfg(not(feature = "no-follow-symlinks"))]
+    {
+        let mut flat = Recorder::default();
+        let opts = CopyOptions {
+            content_only: true,
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        copy_recursive_to_sink(&src, &mut flat, &opts).unwrap();
+        flat.entries.sort();
+        assert_eq!(flat.entries, ["dir sub", "file alias inner", "file sub/inner.txt inner"]);
+        assert!(!base.join("sub").exists());
+    }
+    println!("  [OK] Entries: {:?}", sink.entries);
+}
+
+#[test]
+fn test_bandwidth_throttle() {
+    use std::time::Instant;
+
+    let base = TestDir::new("recursive_copy_test_throttle");
+    let src = base.join("src");
+
+    fs::create_dir_all(&src).unwrap();
+    for i in 0..4 {
+        fs::write(src.join(format!("chunk{i}.bin")), vec![i as u8; 64 * 1024]).unwrap();
+    }
+
+    println!("--- Running Test: Bandwidth Throttle ---");
+    let opts = CopyOptions {
+        max_bytes_per_sec: Some(1024 * 1024),
+        threads: 2,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).expect("Throttled copy failed");
+    let elapsed = started.elapsed();
+
+    assert_eq!(stats.bytes_copied, 256 * 1024);
+    assert!(elapsed.as_millis() >= 200, "256 KiB at 1 MiB/s took only {elapsed:?}");
+    println!("  [OK] Copied {} bytes in {:?}", stats.bytes_copied, elapsed);
+}
+
+#[test]
+fn test_fsync_copy() {
+    let base = TestDir::new("recursive_copy_test_fsync");
+    let src = base.join("src");
+
+    create_file(&src.join("a/b/durable.txt"), "on disk");
+
+    println!("--- Running Test: Fsync ---");
+    let opts = CopyOptions {
+        fsync: true,
+        atomic: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).expect("Fsync copy failed");
+    assert_eq!(stats.files_copied, 1);
+    assert_eq!(fs::read_to_string(base.join("dst/a/b/durable.txt")).unwrap(), "on disk\n");
+
+    copy_recursive(&src.join("a/b/durable.txt"), &base.join("single.txt"), &opts).expect("Fsync file copy failed");
+    assert!(base.join("single.txt").exists());
+
+    // Directories are opened with O_DIRECTORY, so a regular file is rejected.
+    assert!(sys::sync_dir(&base.join("single.txt")).is_err());
+    println!("  [OK] Fsync stats: {:?}", stats);
+}
+
+#[test]
+fn test_depth_limits() {
+    let base = TestDir::new("recursive_copy_test_depth");
+    let src = base.join("src");
+
+    create_file(&src.join("top.txt"), "level 1");
+    create_file(&src.join("l1/mid.txt"), "level 2");
+    create_file(&src.join("l1/l2/deep.txt"), "level 3");
+
+    println!("--- Running Test: Depth Limits ---");
+    let expected: [&[&str]; 4] = [
+        &[],
+        &["l1", "top.txt"],
+        &["l1", "l1/l2", "l1/mid.txt", "top.txt"],
+        &["l1", "l1/l2", "l1/l2/deep.txt", "l1/mid.txt", "top.txt"],
+    ];
+    for (depth, want) in expected.iter().enumerate() {
+        let dst = base.join(format!("depth{depth}"));
+        let opts = CopyOptions { depth, ..Default::default() };
+        copy_recursive(&src, &dst, &opts).expect("Depth-limited copy failed");
+
+        let mut found: Vec<String> = WalkDir::new(&dst)
+            .unwrap()
+            .map(|e| e.unwrap().path().strip_prefix(&dst).unwrap().display().to_string())
+            .collect();
+        found.sort();
+        assert_eq!(found, *want, "depth {depth}");
+        println!("  [OK] depth {}: {:?}", depth, found);
+    }
+
+    let truncated = |depth| {
+        let opts = CopyOptions { depth, dry_run: true, ..Default::default() };
+        copy_recursive(&src, &base.join("dry"), &opts).unwrap().truncated_dirs
+    };
+    assert_eq!(truncated(0), vec![src.clone()]);
+    assert_eq!(truncated(1), vec![src.join("l1")]);
+    assert_eq!(truncated(2), vec![src.join("l1/l2")]);
+    assert!(truncated(3).is_empty());
+
+    let strict = CopyOptions { depth: 2, fail_on_depth: true, ..Default::default() };
+    match copy_recursive(&src, &base.join("strict"), &strict).map_err(CopyError::from) {
+        Err(CopyError::DepthExceeded(p)) => assert_eq!(p, src.join("l1/l2")),
+        other => panic!("Expected DepthExceeded, got {:?}", other),
+    }
+    println!("  [OK] Truncated directories reported");
+}
+
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_file_size_limits() {
+    let base = TestDir::new("recursive_copy_test_size_limits");
+    let src = base.join("src");
+
+    fs::create_dir_all(&src).unwrap();
+    for (name, size) in [("tiny.bin", 10), ("medium.bin", 100), ("huge.bin", 1000)] {
+        fs::write(src.join(name), vec![0u8; size]).unwrap();
+    }
+    unix_fs::symlink("huge.bin", src.join("huge_link")).unwrap();
+
+    println!("--- Running Test: File Size Limits ---");
+    let dst = base.join("dst");
+    let opts = CopyOptions {
+        max_file_size: Some(500),
+        min_file_size: Some(50),
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).expect("Size-limited copy failed");
+
+    assert!(dst.join("medium.bin").exists());
+    assert!(!dst.join("tiny.bin").exists());
+    assert!(!dst.join("huge.bin").exists());
+    assert!(!dst.join("huge_link").exists());
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 3));
+    println!("  [OK] Size limits: {:?}", stats);
+}
+
+#[test]
+fn test_backup_modes() {
+    let base = TestDir::new("recursive_copy_test_backup");
+    let src = base.join("src.txt");
+    fs::create_dir_all(&*base).unwrap();
+
+    println!("--- Running Test: Backup Modes ---");
+    for (mode, atomic) in [(BackupMode::Simple, false), (BackupMode::Simple, true), (BackupMode::Numbered, false)] {
+        let dst = base.join(format!("{mode:?}-{atomic}.txt"));
+        let opts = CopyOptions {
+            overwrite: true,
+            backup: mode,
+            atomic,
+            ..Default::default()
+        };
+        for round in 1..=3 {
+            fs::write(&src, format!("round {round}")).unwrap();
+            copy_recursive(&src, &dst, &opts).expect("Backup copy failed");
+        }
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "round 3");
+
+        let name = dst.file_name().unwrap().to_str().unwrap();
+        if mode == BackupMode::Simple {
+            assert_eq!(fs::read_to_string(base.join(format!("{name}~"))).unwrap(), "round 2");
+        } else {
+            assert_eq!(fs::read_to_string(base.join(format!("{name}.~1~"))).unwrap(), "round 1");
+            assert_eq!(fs::read_to_string(base.join(format!("{name}.~2~"))).unwrap(), "round 2");
+            assert!(!base.join(format!("{name}.~3~")).exists());
+        }
+        println!("  [OK] {:?} (atomic: {}) kept the previous contents", mode, atomic);
+    }
+}
+
+#[test]
+fn test_sparse_modes() {
+    use std::io::{Seek, SeekFrom};
+
+    let base = TestDir::new("recursive_copy_test_sparse");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+
+    // A file with real holes and one made of explicit zeros.
+    let mut holey = File::create(src.join("holey.img")).unwrap();
+    holey.set_len(1024 * 1024).unwrap();
+    holey.seek(SeekFrom::Start(512 * 1024)).unwrap();
+    holey.write_all(b"middle").unwrap();
+    drop(holey);
+    let mut zeros = vec![0u8; 512 * 1024];
+    zeros.extend_from_slice(b"tail");
+    fs::write(src.join("zeros.img"), &zeros).unwrap();
+
+    if fs::metadata(src.join("holey.img")).unwrap().blocks() * 512 >= 1024 * 1024 {
+        println!("  [SKIP] Filesystem does not support sparse files");
+        return;
+    }
+
+    println!("--- Running Test: Sparse Modes ---");
+    let allocated = |path: PathBuf| fs::metadata(path).unwrap().blocks() * 512;
+    for sparse in [SparseMode::Never, SparseMode::Auto, SparseMode::Always] {
+        let dst = base.join(format!("{sparse:?}"));
+        let opts = CopyOptions {
+            sparse,
+            verify: VerifyMode::Checksum(HashAlgo::Crc32),
+            ..Default::default()
+        };
+        copy_recursive(&src, &dst, &opts).expect("Sparse copy failed");
+
+        for name in ["holey.img", "zeros.img"] {
+            assert_eq!(fs::read(src.join(name)).unwrap(), fs::read(dst.join(name)).unwrap());
+        }
+        let holey = allocated(dst.join("holey.img"));
+        let zeros = allocated(dst.join("zeros.img"));
+        match sparse {
+            SparseMode::Never => assert!(holey >= 1024 * 1024 && zeros >= 512 * 1024),
+            SparseMode::Auto => assert!(holey < 64 * 1024 && zeros >= 512 * 1024),
+            SparseMode::Always => assert!(holey < 64 * 1024 && zeros < 64 * 1024),
+        }
+        println!("  [OK] {:?}: holey={} zeros={} bytes allocated", sparse, holey, zeros);
+    }
+}
+
+#[test]
+fn test_move_recursive() {
+    let base = TestDir::new("recursive_copy_test_move");
+
+    println!("--- Running Test: Move Recursive ---");
+    let tree = |root: &PathBuf| {
+        create_file(&root.join("keep/data.txt"), "data");
+        create_file(&root.join("keep/skip.log"), "log");
+        unix_fs::symlink("keep/data.txt", root.join("link")).unwrap();
+    };
+
+    // Same filesystem: a plain rename, which keeps the inodes.
+    let src = base.join("renamed_src");
+    tree(&src);
+    let ino = fs::metadata(src.join("keep/data.txt")).unwrap().ino();
+    let stats = move_recursive(&src, &base.join("renamed"), &CopyOptions::default()).expect("Rename move failed");
+    assert!(!src.exists());
+    assert_eq!(fs::metadata(base.join("renamed/keep/data.txt")).unwrap().ino(), ino);
+    assert_eq!(stats.files_copied, 0);
+
+    // Filters force a copy; excluded entries stay behind with their parents.
+    let src = base.join("filtered_src");
+    tree(&src);
+    let opts = CopyOptions {
+        exclude: vec!["*.log".into()],
+        ..Default::default()
+    };
+    let stats = move_recursive(&src, &base.join("filtered"), &opts).expect("Filtered move failed");
+    assert_eq!(stats.files_copied, 1);
+    assert!(base.join("filtered/keep/data.txt").exists());
+    assert!(fs::read_link(base.join("filtered/link")).is_ok());
+    assert!(!src.join("keep/data.txt").exists() && !src.join("link").exists());
+    assert!(src.join("keep/skip.log").exists());
+
+    // Nothing is removed when an entry was not copied.
+    let src = base.join("blocked_src");
+    tree(&src);
+    create_file(&base.join("blocked/keep/data.txt"), "already here");
+    let opts = CopyOptions {
+        content_only: true,
+        ..Default::default()
+    };
+    let stats = move_recursive(&src, &base.join("blocked"), &opts).expect("Blocked move failed");
+    assert_eq!(stats.files_skipped, 1);
+    assert!(src.join("keep/data.txt").exists() && src.join("link").exists());
+
+    // A linked source moves as the link, never emptying its target.
+    let real = base.join("real");
+    tree(&real);
+    unix_fs::symlink(&real, base.join("dir_link")).unwrap();
+    let opts = CopyOptions { exclude: vec!["*.log".into()], ..Default::default() };
+    move_recursive(&base.join("dir_link"), &base.join("moved_link"), &opts).expect("Link move failed");
+    assert_eq!(fs::read_link(base.join("moved_link")).unwrap(), real);
+    assert!(fs::symlink_metadata(base.join("dir_link")).is_err());
+    assert!(real.join("keep/data.txt").exists() && real.join("keep/skip.log").exists());
+    println!("  [OK] Move honours filters and skipped entries");
+}
+
+#[test]
+fn test_delete_extraneous() {
+    let base = TestDir::new("recursive_copy_test_mirror");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("kept.txt"), "kept");
+    create_file(&src.join("sub/kept.txt"), "kept");
+    create_file(&dst.join("kept.txt"), "old");
+    create_file(&dst.join("stale.txt"), "stale");
+    create_file(&dst.join("sub/stale.txt"), "stale");
+    create_file(&dst.join("gone/deep/stale.txt"), "stale");
+    create_file(&dst.join("local.cache"), "excluded");
+    create_file(&dst.join("cache/inner.txt"), "excluded dir");
+
+    println!("--- Running Test: Delete Extraneous ---");
+    let opts = CopyOptions {
+        content_only: true,
+        overwrite: true,
+        delete_extraneous: true,
+        exclude: vec!["*.cache".into(), "cache/".into()],
+        ..Default::default()
+    };
+    let dry = copy_recursive(&src, &dst, &CopyOptions { dry_run: true, ..opts.clone() }).unwrap();
+    assert_eq!(dry.entries_deleted, 3);
+    assert!(dst.join("stale.txt").exists());
+
+    let stats = copy_recursive(&src, &dst, &opts).expect("Mirror copy failed");
+    assert_eq!(stats.entries_deleted, 3);
+    assert!(!dst.join("stale.txt").exists());
+    assert!(!dst.join("sub/stale.txt").exists());
+    assert!(!dst.join("gone").exists());
+    assert!(dst.join("local.cache").exists() && dst.join("cache/inner.txt").exists());
+    assert_eq!(fs::read_to_string(dst.join("kept.txt")).unwrap(), "kept\n");
+    println!("  [OK] Mirror stats: {:?}", stats);
+
+    let fresh = base.join("fresh");
+    let dry = copy_recursive(&src, &fresh, &CopyOptions { dry_run: true, ..opts.clone() }).unwrap();
+    assert_eq!((dry.files_copied, dry.entries_deleted), (2, 0));
+    let ops: Vec<_> = plan(&src, &fresh, &opts).collect::<Result<_, _>>().unwrap();
+    assert!(!ops.iter().any(|op| matches!(op, CopyOp::Delete { .. })));
+    assert!(!fresh.exists());
+    println!("  [OK] Dry run and plan into a fresh destination");
+}
+
+#[test]
+fn test_preserve_mode_special_bits() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let base = TestDir::new("recursive_copy_test_mode");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("suid"), "suid");
+    create_file(&src.join("sgid"), "sgid");
+    fs::set_permissions(src.join("suid"), fs::Permissions::from_mode(0o4755)).unwrap();
+    fs::set_permissions(src.join("sgid"), fs::Permissions::from_mode(0o2750)).unwrap();
+    let mode = |p: PathBuf| fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+
+    println!("--- Running Test: Preserve Mode ---");
+    copy_recursive(&src, &dst, &CopyOptions { content_only: true, ..Default::default() }).unwrap();
+    assert_eq!(mode(dst.join("suid")), 0o755);
+    assert_eq!(mode(dst.join("sgid")), 0o750);
+
+    let opts = CopyOptions {
+        content_only: true,
+        overwrite: true,
+        preserve_owner: true,
+        preserve_mode: true,
+        ..Default::default()
+    };
+    copy_recursive(&src, &dst, &opts).expect("Copy with preserve_mode failed");
+    assert_eq!(mode(dst.join("suid")), 0o4755);
+    assert_eq!(mode(dst.join("sgid")), 0o2750);
+    println!("  [OK] Special bits kept after chown");
+}
+
+#[test]
+fn test_owner_id_maps() {
+    use std::collections::HashMap;
+    use std::os::unix::fs::MetadataExt;
+
+    let base = TestDir::new("recursive_copy_test_id_maps");
+    let src = base.join("src");
+    create_file(&src.join("sub/data.txt"), "data");
+    let meta = fs::metadata(src.join("sub/data.txt")).unwrap();
+    let (uid, gid) = (meta.uid(), meta.gid());
+
+    println!("--- Running Test: Owner Id Maps ---");
+    let opts = CopyOptions {
+        preserve_owner: true,
+        uid_map: HashMap::from([(uid + 1, uid)]),
+        strict_id_map: true,
+        ..Default::default()
+    };
+    let err = copy_recursive(&src, &base.join("strict"), &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::UnmappedOwner { uid: Some(u), gid: None, .. } if u == uid));
+    println!("  [OK] Unmapped uid refused: {}", err);
+
+    // Only root can hand files to another user.
+    let (new_uid, new_gid) = if uid == 0 { (12345, 23456) } else { (uid, gid) };
+    let opts = CopyOptions {
+        uid_map: HashMap::from([(uid, new_uid)]),
+        gid_map: HashMap::from([(gid, new_gid)]),
+        ..opts
+    };
+    let dst = base.join("mapped");
+    copy_recursive(&src, &dst, &opts).unwrap();
+    for path in [dst.join("sub"), dst.join("sub/data.txt")] {
+        let meta = fs::metadata(&path).unwrap();
+        assert_eq!((meta.uid(), meta.gid()), (new_uid, new_gid));
+    }
+    println!("  [OK] Owner mapped to {}:{}", new_uid, new_gid);
+}
+
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_follow_symlink_loops() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_symlink_loop");
+    let opts = CopyOptions { follow_symlinks: true, ..Default::default() };
+
+    println!("--- Running Test: Symlink Loops ---");
+    let chain = base.join("chain");
+    create_file(&chain.join("data.txt"), "data");
+    symlink("b", chain.join("a")).unwrap();
+    symlink("a", chain.join("b")).unwrap();
+    let err = copy_recursive(&chain, &base.join("dst1"), &opts).expect_err("File symlink loop must fail").error;
+    match &err {
+        CopyError::SymlinkLoop { at, chain: links } => {
+            let names: Vec<_> = links.iter().map(|p| p.strip_prefix(&chain).unwrap()).collect();
+            assert_eq!(names.len(), 2);
+            assert!(names.contains(&at.strip_prefix(&chain).unwrap()));
+        }
+        other => panic!("expected SymlinkLoop, got {other:?}"),
+    }
+    println!("  [OK] {}", err);
+
+    let tree = base.join("tree");
+    create_file(&tree.join("sub/data.txt"), "data");
+    symlink("..", tree.join("sub/up")).unwrap();
+    let err = copy_recursive(&tree, &base.join("dst2"), &opts).expect_err("Directory loop must fail").error;
+    match &err {
+        CopyError::SymlinkLoop { at, chain } => {
+            assert_eq!(at, &tree.join("sub/up"));
+            assert_eq!(chain, std::slice::from_ref(&tree));
+        }
+        other => panic!("expected SymlinkLoop, got {other:?}"),
+    }
+    println!("  [OK] {}", err);
+
+    let hops = base.join("hops");
+    create_file(&hops.join("data.txt"), "data");
+    symlink("data.txt", hops.join("l0")).unwrap();
+    for i in 1..5 {
+        symlink(format!("l{}", i - 1), hops.join(format!("l{}", i))).unwrap();
+    }
+    copy_recursive(&hops, &base.join("dst3"), &opts).expect("Symlink chain must resolve");
+    assert_eq!(fs::read_to_string(base.join("dst3/l4")).unwrap(), "data\n");
+    assert!(!base.join("dst3/l4").is_symlink());
+    println!("  [OK] Chain of links copied as file");
+}
+
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_restrict_dangling_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_restrict_dangling");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("sub/inside.txt"), "inside");
+    create_file(&base.join("outside.txt"), "outside");
+    symlink("inside.txt", src.join("sub/ok")).unwrap();
+    symlink("../missing.txt", src.join("sub/dangling_in")).unwrap();
+    symlink("../../nowhere", src.join("sub/dangling_out")).unwrap();
+    symlink("../../outside.txt", src.join("sub/escape")).unwrap();
+
+    println!("--- Running Test: Restrict Dangling Symlinks ---");
+    let opts = CopyOptions {
+        follow_symlinks: true,
+        restrict_symlinks: true,
+        content_only: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).expect("Restricted copy failed");
+
+    assert_eq!(fs::read_to_string(dst.join("sub/ok")).unwrap(), "inside\n");
+    assert_eq!(fs::read_link(dst.join("sub/dangling_in")).unwrap(), PathBuf::from("../missing.txt"));
+    assert!(fs::symlink_metadata(dst.join("sub/dangling_out")).is_err());
+    assert!(fs::symlink_metadata(dst.join("sub/escape")).is_err());
+    assert_eq!(stats.symlinks_created, 1);
+    assert_eq!(stats.files_skipped, 2);
+    let mut skipped = stats.skipped_symlinks.clone();
+    skipped.sort();
+    let root = base.canonicalize().unwrap();
+    assert_eq!(skipped, vec![
+        (src.join("sub/dangling_out"), root.join("nowhere")),
+        (src.join("sub/escape"), root.join("outside.txt")),
+    ]);
+    println!("  [OK] Restricted stats: {:?}", stats);
+}
+
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_follow_dangling_symlinks() {
+    let base = TestDir::new("recursive_copy_test_dangling");
+    let src = base.join("src");
+
+    create_file(&src.join("data.txt"), "data");
+    std::os::unix::fs::symlink("/mnt/not-mounted-yet", src.join("mount")).unwrap();
+
+    println!("--- Running Test: Dangling Symlinks ---");
+    let opts = CopyOptions { follow_symlinks: true, content_only: true, ..Default::default() };
+    let stats = copy_recursive(&src, &base.join("kept"), &opts).expect("Dangling link aborted the copy");
+    assert_eq!(fs::read_link(base.join("kept/mount")).unwrap(), PathBuf::from("/mnt/not-mounted-yet"));
+    assert_eq!((stats.files_copied, stats.symlinks_created), (1, 1));
+
+    let opts = CopyOptions { copy_dangling_symlinks: false, ..opts };
+    let stats = copy_recursive(&src, &base.join("skipped"), &opts).unwrap();
+    assert!(fs::symlink_metadata(base.join("skipped/mount")).is_err());
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 1));
+    println!("  [OK] Dangling stats: {:?}", stats);
+}
+
+#[test]
+fn test_on_conflict_callback() {
+    let base = TestDir::new("recursive_copy_test_conflict");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    for name in ["keep.txt", "replace.txt", "rename.txt", "new.txt"] {
+        create_file(&src.join(name), "new");
+    }
+    for name in ["keep.txt", "replace.txt", "rename.txt"] {
+        create_file(&dst.join(name), "old");
+    }
+
+    println!("--- Running Test: Conflict Callback ---");
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .on_conflict(|_src, dst| match dst.file_name().and_then(|n| n.to_str()) {
+            Some("replace.txt") => Conflict::Overwrite,
+            Some("rename.txt") => Conflict::Rename("rename (1).txt".into()),
+            _ => Conflict::Skip,
+        })
+        .build();
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy with conflict callback failed");
+    let read = |name: &str| fs::read_to_string(dst.join(name)).unwrap();
+    assert_eq!(read("keep.txt"), "old\n");
+    assert_eq!(read("replace.txt"), "new\n");
+    assert_eq!(read("rename.txt"), "old\n");
+    assert_eq!(read("rename (1).txt"), "new\n");
+    assert_eq!(read("new.txt"), "new\n");
+    assert_eq!((stats.files_copied, stats.files_skipped), (3, 1));
+
+    let opts = CopyOptions {
+        content_only: true,
+        continue_on_error: true,
+        on_conflict: Some(Arc::new(|_: &Path, _: &Path| Conflict::Abort)),
+        ..Default::default()
+    };
+    let err = copy_recursive(&src, &dst, &opts).expect_err("Abort must stop the copy").error;
+    assert!(matches!(err, CopyError::Aborted(_)));
+    println!("  [OK] {}", err);
+}
+
+#[test]
+fn test_directory_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let base = TestDir::new("recursive_copy_test_dir_mode");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("private/readonly/data.txt"), "data");
+    fs::set_permissions(src.join("private/readonly"), fs::Permissions::from_mode(0o555)).unwrap();
+    fs::set_permissions(src.join("private"), fs::Permissions::from_mode(0o700)).unwrap();
+    fs::set_permissions(&src, fs::Permissions::from_mode(0o750)).unwrap();
+    fs::create_dir_all(&dst).unwrap();
+    fs::set_permissions(&dst, fs::Permissions::from_mode(0o755)).unwrap();
+    let mode = |p: PathBuf| fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+
+    println!("--- Running Test: Directory Permissions ---");
+    let opts = CopyOptions { content_only: true, ..Default::default() };
+    copy_recursive(&src, &dst, &opts).expect("Copy into restrictive directories failed");
+    assert_eq!(mode(dst.clone()), 0o755);
+    assert_eq!(mode(dst.join("private")), 0o700);
+    assert_eq!(mode(dst.join("private/readonly")), 0o555);
+    assert_eq!(fs::read_to_string(dst.join("private/readonly/data.txt")).unwrap(), "data\n");
+
+    copy_recursive(&src, &base.join("fresh"), &CopyOptions::default()).unwrap();
+    assert_eq!(mode(base.join("fresh")), 0o750);
+    println!("  [OK] Source directory modes applied after the contents");
+
+    // Let TestDir clean up when not running as root.
+    fs::set_permissions(src.join("private/readonly"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(dst.join("private/readonly"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(base.join("fresh/private/readonly"), fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn test_overwrite_if_different() {
+    let base = TestDir::new("recursive_copy_test_if_different");
+    let src = base.join("src");
+    let dst = base.join("dst");
+
+    create_file(&src.join("same.txt"), "same");
+    create_file(&src.join("edited.txt"), "new!");
+    create_file(&src.join("longer.txt"), "longer");
+    create_file(&dst.join("same.txt"), "same");
+    create_file(&dst.join("edited.txt"), "old!");
+    create_file(&dst.join("longer.txt"), "short");
+    let same_mtime = fs::metadata(dst.join("same.txt")).unwrap().modified().unwrap();
+
+    println!("--- Running Test: Overwrite If Different ---");
+    let opts = CopyOptions {
+        content_only: true,
+        overwrite_mode: OverwriteMode::IfDifferent,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy with IfDifferent failed");
+    assert_eq!((stats.files_copied, stats.files_skipped), (2, 1));
+    assert_eq!(fs::read_to_string(dst.join("edited.txt")).unwrap(), "new!\n");
+    assert_eq!(fs::read_to_string(dst.join("longer.txt")).unwrap(), "longer\n");
+    assert_eq!(fs::metadata(dst.join("same.txt")).unwrap().modified().unwrap(), same_mtime);
+    println!("  [OK] Unchanged file left alone: {:?}", stats);
+}
+
+#[test]
+fn test_estimate_matches_copy() {
+    let base = TestDir::new("recursive_copy_test_estimate");
+    let src = base.join("src");
+
+    create_file(&src.join("a.txt"), "alpha");
+    create_file(&src.join("sub/b.txt"), "bravo bravo");
+    create_file(&src.join("sub/deep/c.txt"), "charlie");
+    create_file(&src.join("sub/skip.log"), "excluded");
+    std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+
+    println!("--- Running Test: Estimate ---");
+    let opts = CopyOptions { exclude: vec!["*.log".into()], depth: 2, ..Default::default() };
+    let estimate = estimate(&src, &opts).expect("Estimate failed");
+    assert_eq!(estimate, CopyEstimate { total_files: 2, total_dirs: 3, total_bytes: 18 });
+
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+    assert_eq!(
+        (stats.files_copied, stats.dirs_created, stats.bytes_copied),
+        (estimate.total_files, estimate.total_dirs, estimate.total_bytes)
+    );
+    println!("  [OK] Estimate: {:?}", estimate);
+}
+
+#[test]
+fn test_skip_unreadable() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let base = TestDir::new("recursive_copy_test_unreadable");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("ok.txt"), "ok");
+    let opts = CopyOptions { skip_unreadable: true, ..Default::default() };
+
+    println!("--- Running Test: Skip Unreadable ---");
+    let shared = Shared::new(&opts);
+    let mut stats = CopyStats::default();
+    copy_one(&src.join("vanished.txt"), &dst.join("vanished.txt"), &opts, &shared, &mut Vec::new(), &mut stats)
+        .expect("Vanished file must be skipped");
+    assert_eq!(stats.files_skipped, 1);
+    let strict = CopyOptions::default();
+    assert!(copy_one(&src.join("vanished.txt"), &dst.join("v.txt"), &strict, &shared, &mut Vec::new(), &mut stats).is_err());
+
+    if fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0) == 0 {
+        println!("  [SKIP] Permission checks do not apply to root");
+        return;
+    }
+    create_file(&src.join("secret.txt"), "secret");
+    create_file(&src.join("locked/inner.txt"), "inner");
+    fs::set_permissions(src.join("secret.txt"), fs::Permissions::from_mode(0o000)).unwrap();
+    fs::set_permissions(src.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    let copied = copy_recursive(&src, &dst, &opts);
+    fs::set_permissions(src.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+    let stats = copied.expect("Unreadable entries must be skipped");
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 2));
+    assert!(dst.join("ok.txt").exists() && !dst.join("secret.txt").exists());
+    println!("  [OK] Unreadable stats: {:?}", stats);
+}
+
+#[test]
+fn test_preallocate() {
+    use std::os::unix::fs::MetadataExt;
+    use std::process::Command;
+
+    let base = TestDir::new("recursive_copy_test_preallocate");
+    let src = base.join("src");
+    let small = base.join("small");
+    let data: Vec<u8> = (0..1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("big.bin"), &data).unwrap();
+
+    println!("--- Running Test: Preallocate ---");
+    let opts = CopyOptions { preallocate: true, content_only: true, ..Default::default() };
+    copy_recursive(&src, &base.join("dst"), &opts).expect("Preallocated copy failed");
+    let meta = fs::metadata(base.join("dst/big.bin")).unwrap();
+    assert_eq!(meta.len(), data.len() as u64);
+    assert!(meta.blocks() * 512 >= meta.len());
+    assert_eq!(fs::read(base.join("dst/big.bin")).unwrap(), data);
+
+    fs::create_dir_all(&small).unwrap();
+    let mounted = Command::new("mount").args(["-t", "tmpfs", "-o", "size=256k", "none"]).arg(&small).status();
+    if !mounted.is_ok_and(|s| s.success()) {
+        println!("  [SKIP] Cannot mount a tmpfs here");
+        return;
+    }
+    let copied = copy_recursive(&src, &small, &opts).map_err(CopyError::from);
+    let written = fs::metadata(small.join("big.bin")).map(|m| m.len());
+    Command::new("umount").arg(&small).status().ok();
+
+    assert!(matches!(copied, Err(CopyError::Io(ref e)) if e.kind() == std::io::ErrorKind::StorageFull));
+    assert_eq!(written.unwrap(), 0);
+    println!("  [OK] ENOSPC reported before writing");
+}
+
+#[test]
+fn test_top_level_symlink_source() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_top_symlink");
+    let real = base.join("real");
+    let link = base.join("link");
+    let dst = base.join("dst");
+
+    create_file(&real.join("data.txt"), "data");
+    symlink("real", &link).unwrap();
+    symlink("missing", base.join("dangling")).unwrap();
+    fs::create_dir_all(&dst).unwrap();
+
+    println!("--- Running Test: Top-Level Symlink Source ---");
+    #[cfg(not(feature = "no-follow-symlinks"))]
+    {
+        copy_recursive(&link, &dst, &CopyOptions::default()).expect("Dereferenced copy failed");
+        assert!(dst.join("link").is_dir() && !dst.join("link").is_symlink());
+        assert_eq!(fs::read_to_string(dst.join("link/data.txt")).unwrap(), "data\n");
+        println!("  [OK] Default copies the target's contents under the link name");
+    }
+
+    let opts = CopyOptions { follow_top_level_symlink: false, ..Default::default() };
+    let stats = copy_recursive(&link, &dst.join("kept"), &opts).expect("Link copy failed");
+    assert_eq!(fs::read_link(dst.join("kept")).unwrap(), PathBuf::from("real"));
+    assert_eq!(stats.symlinks_created, 1);
+
+    copy_recursive(&base.join("dangling"), &dst, &opts).expect("Dangling link copy failed");
+    assert_eq!(fs::read_link(dst.join("dangling")).unwrap(), PathBuf::from("missing"));
+    #[cfg(not(feature = "no-follow-symlinks"))]
+    {
+        let err = copy_recursive(&base.join("dangling"), &dst, &CopyOptions::default()).unwrap_err().error;
+        assert!(matches!(err, CopyError::SrcNotFound(_)));
+    }
+    println!("  [OK] Link recreated without following it");
+}
+
+#[cfg(feature = "log")]
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_log_facade() {
+    use std::sync::Mutex;
+
+    static MESSAGES: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            MESSAGES.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+        fn flush(&self) {}
+    }
+
+    let base = TestDir::new("recursive_copy_test_log");
+    let src = base.join("src");
+    create_file(&src.join("data.txt"), "data");
+    create_file(&base.join("outside.txt"), "outside");
+    std::os::unix::fs::symlink("../outside.txt", src.join("escape")).unwrap();
+
+    println!("--- Running Test: Log Facade ---");
+    log::set_logger(&Capture).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+    let opts = CopyOptions { follow_symlinks: true, restrict_symlinks: true, ..Default::default() };
+    copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(messages.iter().any(|(level, msg)| *level == log::Level::Warn && msg.starts_with("Skipping symlink outside")));
+    assert!(messages.iter().any(|(level, msg)| *level == log::Level::Debug && msg.starts_with("Copied ")));
+    println!("  [OK] Captured {} messages", messages.len());
+}
+
+#[test]
+fn test_retry_transient_errors() {
+    use std::io::{Error, ErrorKind};
+    use std::time::Duration;
+
+    println!("--- Running Test: Retry Transient Errors ---");
+    let opts = CopyOptions { retries: 2, retry_delay: Duration::from_millis(1), ..Default::default() };
+    let flaky = |failures: Vec<ErrorKind>| {
+        let mut failures = failures.into_iter();
+        let mut calls = 0;
+        let res = retry(&opts, || {
+            calls += 1;
+            failures.next().map_or(Ok(calls), |kind| Err(Error::from(kind)))
+        });
+        (res.map_err(|e| e.kind()), calls)
+    };
+
+    assert_eq!(flaky(vec![ErrorKind::TimedOut, ErrorKind::WouldBlock]), (Ok(3), 3));
+    assert_eq!(flaky(vec![ErrorKind::TimedOut; 3]), (Err(ErrorKind::TimedOut), 3));
+    assert_eq!(flaky(vec![ErrorKind::Interrupted; 5]), (Ok(6), 6));
+    assert_eq!(flaky(vec![ErrorKind::StorageFull]), (Err(ErrorKind::StorageFull), 1));
+    assert_eq!(flaky(vec![ErrorKind::PermissionDenied]), (Err(ErrorKind::PermissionDenied), 1));
+    println!("  [OK] Only transient errors are retried");
+}
+
+#[test]
+fn test_copy_destination_path() {
+    let base = TestDir::new("recursive_copy_test_destination");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::create_dir_all(&dst).unwrap();
+    create_file(&src.join("file.txt"), "file");
+
+    println!("--- Running Test: Copy Destination Path ---");
+    let opts = CopyOptions::default();
+    let stats = copy_recursive(&src.join("file.txt"), &dst, &opts).unwrap();
+    assert_eq!(stats.destination, dst.join("file.txt"));
+
+    let stats = copy_recursive(&src.join("file.txt"), &base.join("renamed.txt"), &opts).unwrap();
+    assert_eq!(stats.destination, base.join("renamed.txt"));
+
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(stats.destination, dst.join("src"));
+
+    let opts = CopyOptions { content_only: true, ..Default::default() };
+    let stats = copy_recursive(&src, &base.join("flat"), &opts).unwrap();
+    assert_eq!(stats.destination, base.join("flat"));
+    println!("  [OK] Resolved destinations reported");
+}
+
+#[test]
+fn test_flatten() {
+    let base = TestDir::new("recursive_copy_test_flatten");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("a")).unwrap();
+    fs::create_dir_all(src.join("b/deep")).unwrap();
+    create_file(&src.join("photo.jpg"), "top");
+    create_file(&src.join("a/photo.jpg"), "a");
+    create_file(&src.join("b/deep/photo.jpg"), "deep");
+    create_file(&src.join("b/.hidden"), "hidden");
+    std::os::unix::fs::symlink("photo.jpg", src.join("a/link")).unwrap();
+
+    println!("--- Running Test: Flatten ---");
+    let opts = CopyOptions { flatten: true, content_only: true, ..Default::default() };
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(stats.files_copied, 4);
+    assert_eq!(stats.dirs_created, 1);
+
+    let mut names: Vec<String> = fs::read_dir(&dst).unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, [".hidden", "link", "photo (1).jpg", "photo (2).jpg", "photo.jpg"]);
+
+    let mut contents: Vec<String> = ["photo.jpg", "photo (1).jpg", "photo (2).jpg"].iter()
+        .map(|name| fs::read_to_string(dst.join(name)).unwrap())
+        .collect();
+    contents.sort();
+    assert_eq!(contents, ["a\n", "deep\n", "top\n"]);
+    assert_eq!(fs::read_link(dst.join("link")).unwrap(), PathBuf::from("photo.jpg"));
+    println!("  [OK] Tree flattened with numbered collisions");
+}
+
+#[test]
+fn test_copyignore_files() {
+    let base = TestDir::new("recursive_copy_test_copyignore");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("build")).unwrap();
+    fs::create_dir_all(src.join("sub/cache")).unwrap();
+    fs::create_dir_all(src.join("sub/logs")).unwrap();
+    create_file(&src.join(".copyignore"), "# top level\n*.log\n!keep.log\nbuild/\n");
+    create_file(&src.join("app.log"), "log");
+    create_file(&src.join("keep.log"), "keep");
+    create_file(&src.join("build/out.o"), "obj");
+    create_file(&src.join("sub/.copyignore"), "/cache\nlogs/\n!app.log\n");
+    create_file(&src.join("sub/app.log"), "nested log");
+    create_file(&src.join("sub/cache/blob"), "blob");
+    create_file(&src.join("sub/logs/x"), "x");
+    create_file(&src.join("cache"), "top cache file");
+
+    println!("--- Running Test: .copyignore Files ---");
+    let opts = CopyOptions { respect_ignore_files: true, content_only: true, ..Default::default() };
+    copy_recursive(&src, &dst, &opts).unwrap();
+
+    assert!(!dst.join("app.log").exists());
+    assert!(dst.join("keep.log").exists());
+    assert!(!dst.join("build").exists());
+    assert!(dst.join("sub/app.log").exists(), "nested file re-includes");
+    assert!(!dst.join("sub/cache").exists());
+    assert!(!dst.join("sub/logs").exists());
+    assert!(dst.join("cache").exists(), "anchored pattern stays in its directory");
+    assert!(dst.join(".copyignore").exists());
+    println!("  [OK] Nested ignore files applied");
+
+    let stats = copy_recursive(&src, &base.join("plain"), &CopyOptions { content_only: true, ..Default::default() }).unwrap();
+    assert_eq!(stats.files_copied, 9);
+    println!("  [OK] Ignore files unused by default");
+}
+
+#[test]
+fn test_path_transform() {
+    let base = TestDir::new("recursive_copy_test_path_transform");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("Docs/Old")).unwrap();
+    create_file(&src.join("README.TXT"), "readme");
+    create_file(&src.join("Docs/Guide.MD"), "guide");
+    create_file(&src.join("Docs/Old/Notes.txt"), "notes");
+    create_file(&src.join("skip.tmp"), "tmp");
+
+    println!("--- Running Test: Path Transform ---");
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .path_transform(|rel| {
+            if rel.starts_with("Docs/Old") || rel.extension().is_some_and(|e| e == "tmp") {
+                return None;
+            }
+            Some(PathBuf::from(rel.to_string_lossy().to_lowercase()))
+        })
+        .build();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(stats.files_copied, 2);
+    assert_eq!(fs::read_to_string(dst.join("readme.txt")).unwrap(), "readme\n");
+    assert_eq!(fs::read_to_string(dst.join("docs/guide.md")).unwrap(), "guide\n");
+    assert!(!dst.join("Docs").exists());
+    assert!(!dst.join("docs/old").exists());
+    assert!(!dst.join("skip.tmp").exists());
+    println!("  [OK] Entries remapped and skipped");
+
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .continue_on_error(true)
+        .path_transform(|rel| Some(Path::new("..").join(rel)))
+        .build();
+    let stats = copy_recursive(&src, &base.join("escape"), &opts).unwrap();
+    assert_eq!(stats.files_copied, 0);
+    assert!(!stats.errors.is_empty());
+    assert!(!base.join("README.TXT").exists());
+    println!("  [OK] Escaping results rejected");
+}
+
+#[test]
+fn test_prune_empty_dirs() {
+    let base = TestDir::new("recursive_copy_test_prune_empty");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("logs/old/older")).unwrap();
+    fs::create_dir_all(src.join("keep/empty")).unwrap();
+    fs::create_dir_all(dst.join("existing")).unwrap();
+    fs::create_dir_all(src.join("existing")).unwrap();
+    create_file(&src.join("logs/a.log"), "a");
+    create_file(&src.join("logs/old/older/b.log"), "b");
+    create_file(&src.join("keep/data.txt"), "data");
+
+    println!("--- Running Test: Prune Empty Dirs ---");
+    let opts = CopyOptions {
+        content_only: true,
+        exclude: vec!["*.log".into()],
+        prune_empty_dirs: true,
+        ..Default::default()
+    };
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert!(dst.join("keep/data.txt").exists());
+    assert!(!dst.join("logs").exists());
+    assert!(!dst.join("keep/empty").exists());
+    assert!(dst.join("existing").is_dir(), "pre-existing directories are kept");
+    assert_eq!(stats.dirs_created, 1);
+    println!("  [OK] Empty directories pruned bottom-up");
+}
+
+#[test]
+fn test_cancel_flag() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let base = TestDir::new("recursive_copy_test_cancel");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+    create_file(&src.join("small.txt"), "small");
+    File::create(src.join("big.bin")).unwrap().write_all(&vec![7u8; 1 << 20]).unwrap();
+
+    println!("--- Running Test: Cancel Flag ---");
+    let cancel = Arc::new(AtomicBool::new(true));
+    let opts = CopyOptions { cancel: Some(cancel.clone()), continue_on_error: true, ..Default::default() };
+    let err = copy_recursive(&src, &base.join("dst"), &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::Cancelled));
+    println!("  [OK] Pre-set flag cancels the walk");
+
+    cancel.store(false, Ordering::Relaxed);
+    let flag = cancel.clone();
+    let opts = CopyOptions::builder()
+        .atomic(true)
+        .buffer_size(64 * 1024)
+        .cancel(cancel.clone())
+        .progress(move |event| {
+            if let ProgressEvent::BytesCopied { so_far, .. } = event
+                && *so_far >= 64 * 1024
+            {
+                flag.store(true, Ordering::Relaxed);
+            }
+        })
+        .build();
+    let dst = base.join("big.bin");
+    let err = copy_recursive(&src.join("big.bin"), &dst, &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::Cancelled));
+    assert!(!dst.exists());
+    assert_eq!(fs::read_dir(&*base).unwrap().count(), 2, "temporary file removed");
+    println!("  [OK] Cancelled mid-file without partial output");
+}
+
+#[test]
+fn test_symlink_owner_and_mode() {
+    use std::os::unix::fs::{MetadataExt, lchown, symlink};
+
+    let base = TestDir::new("recursive_copy_test_symlink_meta");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+    create_file(&src.join("target.txt"), "target");
+    symlink("target.txt", src.join("link")).unwrap();
+
+    println!("--- Running Test: Symlink Owner and Mode ---");
+    let root = fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0) == 0;
+    if root {
+        lchown(src.join("link"), Some(65534), Some(65534)).unwrap();
+    }
+    let opts = CopyOptions { preserve_owner: true, preserve_mode: true, ..Default::default() };
+    let dst = base.join("dst");
+    copy_recursive(&src, &dst, &opts).unwrap();
+
+    let link = fs::symlink_metadata(dst.join("link")).unwrap();
+    assert!(link.file_type().is_symlink());
+    assert_eq!(link.mode() & 0o7777, fs::symlink_metadata(src.join("link")).unwrap().mode() & 0o7777);
+    if root {
+        assert_eq!((link.uid(), link.gid()), (65534, 65534));
+        assert_eq!(fs::metadata(dst.join("target.txt")).unwrap().uid(), 0, "target keeps its own owner");
+    }
+    println!("  [OK] Link metadata taken from the link itself");
+}
+
+#[test]
+fn test_max_total_bytes() {
+    let base = TestDir::new("recursive_copy_test_total_budget");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+    for i in 0..4 {
+        File::create(src.join(format!("f{i}.bin"))).unwrap().write_all(&[0u8; 100]).unwrap();
+    }
+
+    println!("--- Running Test: Max Total Bytes ---");
+    let opts = CopyOptions { max_total_bytes: Some(250), continue_on_error: true, ..Default::default() };
+    let err = copy_recursive(&src, &base.join("dst"), &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::BudgetExceeded(_)));
+    assert_eq!(fs::read_dir(base.join("dst")).unwrap().count(), 2);
+    println!("  [OK] Copy stopped before the file over budget");
+
+    let opts = CopyOptions { max_total_bytes: Some(400), dry_run: true, ..Default::default() };
+    let stats = copy_recursive(&src, &base.join("dry"), &opts).unwrap();
+    assert_eq!(stats.bytes_copied, 400);
+    println!("  [OK] Exact fit allowed");
+}
+
+#[test]
+fn test_partial_stats_on_error() {
+    let base = TestDir::new("recursive_copy_test_partial_stats");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+    for i in 0..3 {
+        File::create(src.join(format!("f{i}.bin"))).unwrap().write_all(&[0u8; 100]).unwrap();
+    }
+
+    println!("--- Running Test: Partial Stats On Error ---");
+    let opts = CopyOptions { max_total_bytes: Some(250), ..Default::default() };
+    let failure = copy_recursive(&src, &base.join("dst"), &opts).unwrap_err();
+    assert!(matches!(failure.error, CopyError::BudgetExceeded(_)));
+    assert_eq!(failure.stats.files_copied, 2);
+    assert_eq!(failure.stats.bytes_copied, 200);
+    assert_eq!(failure.stats.destination, base.join("dst"));
+    assert!(failure.to_string().contains("after 2 files, 200 bytes"));
+    println!("  [OK] Failure carries the files copied before it");
+
+    let srcs = [src.join("f0.bin"), src.join("f1.bin"), src.join("f2.bin")];
+    let failure = copy_many(&srcs, &base.join("many"), &opts).unwrap_err();
+    assert_eq!(failure.stats.files_copied, 2);
+    assert_eq!(failure.stats.destination, base.join("many"));
+    println!("  [OK] copy_many carries the sources copied before it");
+
+    // `max_files` rules out the rename, so the move copies and hits the budget.
+    let opts = CopyOptions { max_files: Some(10), ..opts };
+    let failure = move_recursive(&src, &base.join("moved"), &opts).unwrap_err();
+    assert_eq!(failure.stats.files_copied, 2);
+    assert_eq!(failure.stats.destination, base.join("moved"));
+    assert!(src.join("f0.bin").exists());
+    println!("  [OK] move_recursive carries the files copied before it");
+
+    let err: CopyError = copy_recursive(&src, &base.join("again"), &opts).unwrap_err().into();
+    assert!(matches!(err, CopyError::BudgetExceeded(_)));
+    println!("  [OK] Converts back into the CopyError");
+}
+
+#[test]
+fn test_max_files() {
+    let base = TestDir::new("recursive_copy_test_max_files");
+    let src = base.join("src");
+    for i in 0..5 {
+        create_file(&src.join(format!("d{}/f{i}.txt", i % 2)), "data");
+    }
+
+    println!("--- Running Test: Max Files ---");
+    let dst = base.join("dst");
+    let opts = CopyOptions { max_files: Some(2), content_only: true, ..Default::default() };
+    let mut copied = 0;
+    for _ in 0..2 {
+        let stats = copy_recursive(&src, &dst, &opts).unwrap();
+        assert_eq!(stats.files_copied, 2);
+        assert!(stats.truncated);
+        copied += stats.files_copied;
+    }
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 4));
+    assert!(!stats.truncated);
+    assert_eq!(copied + stats.files_copied, 5);
+    println!("  [OK] Tree copied two files per run");
+
+    let opts = CopyOptions { max_files: Some(3), threads: 4, ..opts };
+    let stats = copy_recursive(&src, &base.join("parallel"), &opts).unwrap();
+    assert_eq!(stats.files_copied, 3);
+    assert!(stats.truncated);
+    println!("  [OK] Limit shared by worker threads");
+}
+
+#[test]
+fn test_verify_tree() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_verify_tree");
+    let src = base.join("src");
+    create_file(&src.join("a.txt"), "alpha");
+    create_file(&src.join("b.txt"), "beta");
+    create_file(&src.join("sub/c.txt"), "gamma");
+    create_file(&src.join("sub/d.txt"), "delta");
+    create_file(&src.join("skip.log"), "log");
+    symlink("a.txt", src.join("link")).unwrap();
+
+    println!("--- Running Test: Verify Tree ---");
+    let opts = CopyOptions { exclude: vec!["*.log".into()], ..Default::default() };
+    let dst = copy_recursive(&src, &base.join("dst"), &opts).unwrap().destination;
+    assert_eq!(verify_tree(&src, &dst, &opts).unwrap(), []);
+    fs::write(dst.join("skip.log"), "not copied").unwrap();
+    assert_eq!(verify_tree(&src, &dst, &opts).unwrap(), []);
+    println!("  [OK] Fresh copy matches");
+
+    fs::remove_file(dst.join("b.txt")).unwrap();
+    fs::write(dst.join("a.txt"), "ALPHA\n").unwrap();
+    fs::write(dst.join("sub/c.txt"), "gamma, longer\n").unwrap();
+    fs::remove_file(dst.join("sub/d.txt")).unwrap();
+    fs::create_dir(dst.join("sub/d.txt")).unwrap();
+    fs::remove_file(dst.join("link")).unwrap();
+    symlink("b.txt", dst.join("link")).unwrap();
+    create_file(&dst.join("new/extra.txt"), "extra");
+
+    let mut found = verify_tree(&src, &dst, &opts).unwrap();
+    found.sort_by_key(|m| format!("{m:?}"));
+    assert_eq!(found, [
+        Mismatch::Extra(PathBuf::from("new")),
+        Mismatch::Kind(PathBuf::from("sub/d.txt")),
+        Mismatch::LinkTarget { path: "link".into(), expected: "a.txt".into(), found: "b.txt".into() },
+        Mismatch::Missing(PathBuf::from("b.txt")),
+        Mismatch::Size { path: "sub/c.txt".into(), expected: 6, found: 14 },
+    ]);
+    println!("  [OK] Size-only mismatches: {:?}", found);
+
+    let checked = CopyOptions { verify: VerifyMode::Checksum(HashAlgo::Crc32), ..opts };
+    let found = verify_tree(&src, &dst, &checked).unwrap();
+    assert!(found.iter().any(|m| matches!(m, Mismatch::Content { path, .. } if path == Path::new("a.txt"))));
+    assert_eq!(verify_tree(&src.join("a.txt"), &src.join("a.txt"), &checked).unwrap(), []);
+    println!("  [OK] Checksums catch same-size changes");
+}
+
+#[test]
+fn test_symlinked_destination() {
+    let base = TestDir::new("recursive_copy_test_dst_symlink");
+    let src = base.join("src");
+    let real = base.join("real");
+    let link = base.join("link");
+    fs::create_dir_all(&src).unwrap();
+    fs::create_dir_all(&real).unwrap();
+    create_file(&src.join("a.txt"), "a");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    println!("--- Running Test: Symlinked Destination ---");
+    let stats = copy_recursive(&src, &link, &CopyOptions::default()).unwrap();
+    assert_eq!(fs::read_to_string(real.join("src/a.txt")).unwrap(), "a\n");
+    assert_eq!(stats.destination, link.join("src"));
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    println!("  [OK] Symlinked destination followed by default");
+
+    let opts = CopyOptions { follow_dst_symlink: false, ..Default::default() };
+    let err = copy_recursive(&src.join("a.txt"), &link, &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::DestIsSymlink(p) if p == link));
+    let err = move_recursive(&src, &link, &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::DestIsSymlink(_)));
+    assert!(src.join("a.txt").exists());
+    assert!(!real.join("a.txt").exists());
+    println!("  [OK] Symlinked destination rejected");
+}
+
+#[test]
+fn test_preserve_acl() {
+    let base = TestDir::new("recursive_copy_test_acl");
+    let src = base.join("src");
+    fs::create_dir_all(src.join("shared")).unwrap();
+    create_file(&src.join("shared/doc.txt"), "doc");
+
+    // user::rw- user:65534:r-- group::r-- mask::r-- other::r--
+    let mut acl = 2u32.to_le_bytes().to_vec();
+    for (tag, perm, id) in [(0x01u16, 6u16, u32::MAX), (0x02, 4, 65534), (0x04, 4, u32::MAX), (0x10, 4, u32::MAX), (0x20, 4, u32::MAX)] {
+        acl.extend(tag.to_le_bytes());
+        acl.extend(perm.to_le_bytes());
+        acl.extend(id.to_le_bytes());
+    }
+
+    println!("--- Running Test: Preserve ACL ---");
+    if let Err(e) = sys::set_xattr(&src.join("shared/doc.txt"), sys::ACL_ACCESS, &acl) {
+        println!("  [SKIP] ACLs not supported here: {e}");
+        return;
+    }
+    sys::set_xattr(&src.join("shared"), sys::ACL_DEFAULT, &acl).unwrap();
+
+    let opts = CopyOptions { preserve_acl: true, ..Default::default() };
+    copy_recursive(&src, &base.join("with"), &opts).unwrap();
+    assert_eq!(sys::get_xattr(&base.join("with/shared/doc.txt"), sys::ACL_ACCESS).unwrap(), acl);
+    assert_eq!(sys::get_xattr(&base.join("with/shared"), sys::ACL_DEFAULT).unwrap(), acl);
+    println!("  [OK] Access and default ACLs copied");
+
+    copy_recursive(&src, &base.join("without"), &CopyOptions::default()).unwrap();
+    let err = sys::get_xattr(&base.join("without/shared/doc.txt"), sys::ACL_ACCESS).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(sys::ENODATA));
+    println!("  [OK] ACLs dropped by default");
+}
+
+#[test]
+fn test_on_dir_callback() {
+    use std::sync::Mutex;
+
+    let base = TestDir::new("recursive_copy_test_on_dir");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    fs::create_dir_all(src.join("keep/inner")).unwrap();
+    fs::create_dir_all(src.join("shallow/inner")).unwrap();
+    fs::create_dir_all(src.join("gone/inner")).unwrap();
+    for dir in ["keep", "keep/inner", "shallow", "shallow/inner", "gone", "gone/inner"] {
+        create_file(&src.join(dir).join("f.txt"), dir);
+    }
+
+    println!("--- Running Test: On Dir Callback ---");
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let src_root = src.clone();
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .on_dir(move |src_dir, dst_dir| {
+            let rel = src_dir.strip_prefix(&src_root).unwrap().to_path_buf();
+            log.lock().unwrap().push((rel.clone(), dst_dir.to_path_buf()));
+            match rel.to_str().unwrap() {
+                "shallow" => DirAction::Skip,
+                "gone" => DirAction::SkipRecursive,
+                _ => DirAction::Descend,
+            }
+        })
+        .build();
+    copy_recursive(&src, &dst, &opts).unwrap();
+
+    assert!(dst.join("keep/inner/f.txt").exists());
+    assert!(dst.join("shallow").is_dir());
+    assert_eq!(fs::read_dir(dst.join("shallow")).unwrap().count(), 0);
+    assert!(!dst.join("gone").exists());
+
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort();
+    let expected: Vec<(PathBuf, PathBuf)> = ["gone", "keep", "keep/inner", "shallow"].iter()
+        .map(|rel| (PathBuf::from(rel), dst.join(rel)))
+        .collect();
+    assert_eq!(seen, expected);
+    println!("  [OK] Directories descended, skipped, and pruned");
+}
+
+#[test]
+fn test_same_filesystem() {
+    let base = TestDir::new("recursive_copy_test_same_fs");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+
+    println!("--- Running Test: Same Filesystem ---");
+    assert!(same_filesystem(&src, &base.join("missing/dst")).unwrap());
+    assert!(!same_filesystem(&src, Path::new("/proc/self")).unwrap());
+    assert!(same_filesystem(&base.join("missing"), &src).is_err());
+    println!("  [OK] Devices compared through the nearest existing ancestor");
+}
+
+#[test]
+fn test_trailing_slash_copies_contents() {
+    let base = TestDir::new("recursive_copy_test_trailing_slash");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("a.txt"), "a");
+    fs::create_dir_all(&dst).unwrap();
+
+    println!("--- Running Test: Trailing Slash ---");
+    let mut with_slash = src.clone().into_os_string();
+    with_slash.push("/");
+    copy_recursive(Path::new(&with_slash), &dst, &CopyOptions::default()).unwrap();
+    assert!(dst.join("a.txt").is_file());
+    assert!(!dst.join("src").exists());
+
+    copy_recursive(&src, &dst, &CopyOptions::default()).unwrap();
+    assert!(dst.join("src/a.txt").is_file());
+    println!("  [OK] src/ copies the contents, src the directory itself");
+}
+
+#[test]
+fn test_resume_partial_file() {
+    let base = TestDir::new("recursive_copy_test_resume");
+    let src = base.join("big.bin");
+    let dst = base.join("out.bin");
+    let part = base.join("out.bin.part");
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::create_dir_all(&*base).unwrap();
+    fs::write(&src, &data).unwrap();
+
+    println!("--- Running Test: Resume Partial File ---");
+    let opts = CopyOptions::builder()
+        .resume(true)
+        .buffer_size(8192)
+        .verify(VerifyMode::Checksum(HashAlgo::Sha256))
+        .build();
+    fs::write(&part, &data[..40_000]).unwrap();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(fs::read(&dst).unwrap(), data);
+    assert_eq!(stats.bytes_copied, 60_000);
+    assert!(!part.exists());
+    println!("  [OK] Resumed after the matching prefix");
+
+    // A corrupted chunk is copied again, along with everything after it.
+    let mut corrupt = data[..40_000].to_vec();
+    corrupt[20_000] ^= 0xff;
+    fs::write(&part, &corrupt).unwrap();
+    fs::remove_file(&dst).unwrap();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(fs::read(&dst).unwrap(), data);
+    assert_eq!(stats.bytes_copied, 100_000 - 16_384);
+    println!("  [OK] Mismatching prefix copied again");
+
+    fs::write(&part, &corrupt).unwrap();
+    fs::remove_file(&dst).unwrap();
+    let trusting = CopyOptions::builder().resume(true).trust_partial(true).build();
+    let stats = copy_recursive(&src, &dst, &trusting).unwrap();
+    assert_eq!(fs::read(&dst).unwrap()[20_000], data[20_000] ^ 0xff);
+    assert_eq!(stats.bytes_copied, 60_000);
+    println!("  [OK] trust_partial keeps the prefix unchecked");
+}
+
+#[test]
+fn test_sort_by_name_and_min_depth() {
+    use std::sync::Mutex;
+
+    let base = TestDir::new("recursive_copy_test_sorted");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    for name in ["top.txt", "b/two.txt", "a/one.txt", "a/z/deep.txt", "c.txt"] {
+        create_file(&src.join(name), name);
+    }
+
+    println!("--- Running Test: Sort By Name ---");
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&order);
+    let opts = CopyOptions::builder()
+        .sort_by_name(true)
+        .progress(move |event| {
+            if let ProgressEvent::FileStarted { path, .. } = event {
+                log.lock().unwrap().push(path.to_path_buf());
+            }
+        })
+        .build();
+    copy_recursive(&src, &dst, &opts).unwrap();
+    let expected: Vec<PathBuf> = ["a/one.txt", "a/z/deep.txt", "b/two.txt", "c.txt", "top.txt"].iter()
+        .map(|name| src.join(name))
+        .collect();
+    assert_eq!(*order.lock().unwrap(), expected);
+    println!("  [OK] Files copied in name order");
+
+    println!("--- Running Test: Min Depth ---");
+    let shallow = base.join("shallow");
+    let stats = copy_recursive(&src, &shallow, &CopyOptions::builder().min_depth(2).build()).unwrap();
+    assert!(!shallow.join("top.txt").exists());
+    assert!(!shallow.join("c.txt").exists());
+    assert!(shallow.join("a/one.txt").is_file());
+    assert!(shallow.join("a/z/deep.txt").is_file());
+    assert_eq!(stats.files_copied, 3);
+    println!("  [OK] Files above min_depth left out");
+}
+
+#[test]
+fn test_create_parents() {
+    let base = TestDir::new("recursive_copy_test_create_parents");
+    let src = base.join("src");
+    create_file(&src.join("a.txt"), "a");
+
+    println!("--- Running Test: Create Parents ---");
+    let opts = CopyOptions::builder().create_parents(false).build();
+    let missing = base.join("missing");
+    match copy_recursive(&src.join("a.txt"), &missing.join("a.txt"), &opts).map_err(CopyError::from) {
+        Err(CopyError::DestParentMissing(p)) => assert_eq!(p, missing),
+        other => panic!("Expected DestParentMissing, got {:?}", other),
+    }
+    assert!(matches!(copy_recursive(&src, &missing.join("dst"), &opts).map_err(CopyError::from), Err(CopyError::DestParentMissing(_))));
+    assert!(!missing.exists());
+
+    copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+    assert!(base.join("dst/a.txt").is_file());
+    copy_recursive(&src.join("a.txt"), &missing.join("a.txt"), &CopyOptions::default()).unwrap();
+    assert!(missing.join("a.txt").is_file());
+    println!("  [OK] Missing parents refused unless create_parents is set");
+}
+
+#[test]
+fn test_error_kind() {
+    let base = TestDir::new("recursive_copy_test_error_kind");
+
+    println!("--- Running Test: Error Kind ---");
+    let err = copy_recursive(&base.join("missing"), &base.join("dst"), &CopyOptions::default()).unwrap_err().error;
+    assert_eq!(err.kind(), CopyErrorKind::SrcNotFound);
+    assert_eq!(err.raw_os_error(), None);
+
+    let err = CopyError::from(io::Error::from_raw_os_error(28));
+    assert_eq!(err.kind(), CopyErrorKind::Io(io::ErrorKind::StorageFull));
+    assert_eq!(err.raw_os_error(), Some(28));
+    let walk = CopyError::Walk(WalkError::Io(io::Error::from_raw_os_error(13), PathBuf::from("/unreadable")));
+    assert_eq!(walk.kind(), CopyErrorKind::Io(io::ErrorKind::PermissionDenied));
+    assert_eq!(walk.raw_os_error(), Some(13));
+    println!("  [OK] Errors compared by kind");
+}
+
+#[cfg(not(feature = "no-follow-symlinks"))]
+#[test]
+fn test_max_recursion_depth() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_recursion_depth");
+    let src = base.join("src");
+    create_file(&src.join("top.txt"), "top");
+    create_file(&base.join("d1/one.txt"), "one");
+    create_file(&base.join("d2/two.txt"), "two");
+    symlink(base.join("d1"), src.join("link1")).unwrap();
+    symlink(base.join("d2"), base.join("d1/link2")).unwrap();
+
+    println!("--- Running Test: Max Recursion Depth ---");
+    let dst = base.join("dst");
+    let opts = CopyOptions::builder()
+        .follow_symlinks(true)
+        .max_recursion_depth(1)
+        .continue_on_error(true)
+        .build();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    assert!(dst.join("link1/one.txt").is_file());
+    assert!(!dst.join("link1/link2").exists());
+    assert_eq!(stats.errors.len(), 1);
+    assert_eq!(stats.errors[0].0, base.join("d1/link2"));
+    assert_eq!(stats.errors[0].1.kind(), CopyErrorKind::DepthExceeded);
+
+    let strict = CopyOptions::builder().follow_symlinks(true).max_recursion_depth(0).build();
+    let err = copy_recursive(&src, &base.join("dst0"), &strict).unwrap_err().error;
+    assert!(matches!(err, CopyError::DepthExceeded(p) if p == src.join("link1")));
+    println!("  [OK] Followed directories capped at max_recursion_depth");
+}
+
+#[test]
+fn test_link_instead_of_copy() {
+    use std::os::unix::fs::MetadataExt;
+
+    let base = TestDir::new("recursive_copy_test_link_copy");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("a.txt"), "a");
+    create_file(&src.join("sub/b.txt"), "b");
+    create_file(&dst.join("a.txt"), "old");
+
+    println!("--- Running Test: Link Instead Of Copy ---");
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .link_instead_of_copy(true)
+        .overwrite(true)
+        .build();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    for name in ["a.txt", "sub/b.txt"] {
+        let (s, d) = (fs::metadata(src.join(name)).unwrap(), fs::metadata(dst.join(name)).unwrap());
+        assert_eq!((s.dev(), s.ino()), (d.dev(), d.ino()), "{name}");
+    }
+    assert_eq!(stats.hardlinks_created, 2);
+    assert_eq!((stats.files_copied, stats.bytes_copied), (0, 0));
+    println!("  [OK] Files linked, existing destination replaced");
+}
+
+#[test]
+fn test_metadata_only() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let base = TestDir::new("recursive_copy_test_metadata_only");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("same.txt"), "aaaa");
+    create_file(&src.join("grown.txt"), "longer");
+    create_file(&src.join("missing.txt"), "m");
+    create_file(&dst.join("same.txt"), "bbbb");
+    create_file(&dst.join("grown.txt"), "short");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    let file = File::options().write(true).open(src.join("same.txt")).unwrap();
+    file.set_modified(mtime).unwrap();
+    file.set_permissions(fs::Permissions::from_mode(0o640)).unwrap();
+    fs::set_permissions(dst.join("same.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+
+    println!("--- Running Test: Metadata Only ---");
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .metadata_only(true)
+        .preserve_timestamps(true)
+        .build();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+
+    let meta = fs::metadata(dst.join("same.txt")).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+    assert_eq!(meta.modified().unwrap(), mtime);
+    assert_eq!(fs::read_to_string(dst.join("same.txt")).unwrap(), "bbbb\n");
+    assert_eq!(fs::read_to_string(dst.join("grown.txt")).unwrap(), "short\n");
+    assert!(!dst.join("missing.txt").exists());
+    assert_eq!((stats.metadata_synced, stats.files_skipped, stats.files_copied), (1, 2, 0));
+    println!("  [OK] Metadata applied without touching data");
+}
+
+#[test]
+fn test_creation_time() {
+    let base = TestDir::new("recursive_copy_test_btime");
+    let file = base.join("f.txt");
+    create_file(&file, "f");
+
+    println!("--- Running Test: Creation Time ---");
+    if let Some(created) = creation_time(&file).unwrap() {
+        assert!(created <= SystemTime::now());
+        assert_eq!(Some(created), fs::metadata(&file).unwrap().created().ok());
+    }
+    assert!(creation_time(&base.join("missing")).is_err());
+    println!("  [OK] Birth time read: {:?}", creation_time(&file).unwrap());
+}
+
+#[test]
+fn test_plan() {
+    let base = TestDir::new("recursive_copy_test_plan");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("a.txt"), "a");
+    create_file(&src.join("sub/b.txt"), "b");
+    std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+    create_file(&dst.join("a.txt"), "old");
+
+    println!("--- Running Test: Plan ---");
+    let opts = CopyOptions::builder().content_only(true).sort_by_name(true).build();
+    let ops: Vec<String> = plan(&src, &dst, &opts)
+        .map(|op| match op.unwrap() {
+            CopyOp::CreateDir { dst: d, .. } => format!("dir {}", d.strip_prefix(&dst).unwrap().display()),
+            CopyOp::CopyFile { dst: d, .. } => format!("file {}", d.strip_prefix(&dst).unwrap().display()),
+            CopyOp::Symlink { dst: d, .. } => format!("link {}", d.strip_prefix(&dst).unwrap().display()),
+            CopyOp::Skip { dst: d, reason, .. } => format!("skip {} {:?}", d.strip_prefix(&dst).unwrap().display(), reason),
+            op => panic!("Unexpected {:?}", op),
+        })
+        .collect();
+    assert_eq!(ops, ["skip a.txt Exists", "link link", "dir sub", "file sub/b.txt"]);
+    assert!(!dst.join("sub").exists());
+    println!("  [OK] Planned {:?}", ops);
+
+    let missing: Vec<_> = plan(&base.join("missing"), &dst, &opts).collect();
+    assert!(matches!(missing[..], [Err(CopyError::SrcNotFound(_))]));
+    println!("  [OK] Fatal error ends the plan");
+}
+
+#[test]
+fn test_replace_dest() {
+    let base = TestDir::new("recursive_copy_test_replace_dest");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("a.txt"), "a");
+    create_file(&src.join("sub/x.txt"), "x");
+    create_file(&dst.join("sub"), "file in the way");
+    create_file(&dst.join("a.txt/inner.txt"), "dir in the way");
+
+    println!("--- Running Test: Replace Dest ---");
+    let opts = CopyOptions::builder().content_only(true).replace_dest(true).build();
+    copy_recursive(&src, &dst, &opts).unwrap();
+    assert_eq!(fs::read_to_string(dst.join("a.txt")).unwrap(), "a\n");
+    assert_eq!(fs::read_to_string(dst.join("sub/x.txt")).unwrap(), "x\n");
+    println!("  [OK] Entries of the wrong type replaced");
+
+    let file_dst = base.join("file_dst");
+    create_file(&file_dst, "old");
+    assert!(matches!(copy_recursive(&src, &file_dst, &CopyOptions::default()).map_err(CopyError::from), Err(CopyError::DestNotDir(_))));
+    let backed_up = CopyOptions::builder().replace_dest(true).backup(BackupMode::Simple).build();
+    copy_recursive(&src, &file_dst, &backed_up).unwrap();
+    assert!(file_dst.join("sub/x.txt").is_file());
+    assert_eq!(fs::read_to_string(base.join("file_dst~")).unwrap(), "old\n");
+    println!("  [OK] File destination backed up and replaced by the directory");
+}
+
+#[test]
+fn test_deadline() {
+    use std::time::{Duration, Instant};
+
+    let base = TestDir::new("recursive_copy_test_deadline");
+    let src = base.join("src");
+    fs::create_dir_all(&src).unwrap();
+    File::create(src.join("big.bin")).unwrap().write_all(&vec![7u8; 1 << 20]).unwrap();
+
+    println!("--- Running Test: Deadline ---");
+    let passed = CopyOptions::builder().deadline(Instant::now()).continue_on_error(true).build();
+    let err = copy_recursive(&src, &base.join("dst"), &passed).unwrap_err().error;
+    assert_eq!(err.kind(), CopyErrorKind::TimedOut);
+
+    let slow = CopyOptions::builder()
+        .deadline(Instant::now() + Duration::from_millis(50))
+        .buffer_size(64 * 1024)
+        .progress(|event| {
+            if let ProgressEvent::BytesCopied { .. } = event {
+                thread::sleep(Duration::from_millis(100));
+            }
+        })
+        .build();
+    let err = copy_recursive(&src.join("big.bin"), &base.join("big.bin"), &slow).unwrap_err().error;
+    assert!(matches!(err, CopyError::TimedOut));
+    assert!(fs::metadata(base.join("big.bin")).unwrap().len() < 1 << 20);
+
+    let later = CopyOptions::builder().deadline(Instant::now() + Duration::from_secs(3600)).build();
+    copy_recursive(&src, &base.join("dst"), &later).unwrap();
+    println!("  [OK] Copy stopped at its deadline");
+}
+
+#[test]
+fn test_copy_file_range() {
+    let base = TestDir::new("recursive_copy_test_copy_file_range");
+    let src = base.join("src");
+    let data: Vec<u8> = (0..9 * 1024 * 1024 + 123).map(|i| (i % 251) as u8).collect();
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("big.bin"), &data).unwrap();
+    create_file(&src.join("small.txt"), "small");
+
+    println!("--- Running Test: Copy File Range ---");
+    let opts = CopyOptions::builder()
+        .content_only(true)
+        .copy_file_range(true)
+        .verify(VerifyMode::Checksum(HashAlgo::Sha256))
+        .build();
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+    assert_eq!(fs::read(base.join("dst/big.bin")).unwrap(), data);
+    assert_eq!(fs::read_to_string(base.join("dst/small.txt")).unwrap(), "small\n");
+    assert_eq!(stats.bytes_copied, data.len() as u64 + 6);
+
+    // procfs reports a size of 0, so the buffered loop has to read it all.
+    let stats = copy_recursive(Path::new("/proc/self/status"), &base.join("status"), &opts).unwrap();
+    assert!(stats.bytes_copied > 0);
+    assert!(!fs::read(base.join("status")).unwrap().is_empty());
+    println!("  [OK] Contents copied in the kernel, procfs read through the buffer");
+}
+
+#[test]
+fn test_detect_case_collisions() {
+    let base = TestDir::new("recursive_copy_test_case_collisions");
+    let src = base.join("src");
+    create_file(&src.join("README"), "upper");
+    create_file(&src.join("readme"), "lower");
+    create_file(&src.join("Docs/a.txt"), "a");
+    create_file(&src.join("docs/b.txt"), "b");
+
+    println!("--- Running Test: Detect Case Collisions ---");
+    let opts = CopyOptions::builder().content_only(true).detect_case_collisions(true).sort_by_name(true).build();
+    match copy_recursive(&src, &base.join("dst"), &opts).map_err(CopyError::from) {
+        Err(CopyError::CaseCollision { existing, new }) => {
+            assert_eq!((existing, new), (src.join("Docs"), src.join("docs")));
+        }
+        other => panic!("expected CaseCollision, got {other:?}"),
+    }
+
+    let opts = CopyOptions { continue_on_error: true, ..opts };
+    let stats = copy_recursive(&src, &base.join("dst2"), &opts).unwrap();
+    let kinds: Vec<_> = stats.errors.iter().map(|(_, e)| e.kind()).collect();
+    assert_eq!(kinds, [CopyErrorKind::CaseCollision; 2]);
+    assert_eq!(fs::read_to_string(base.join("dst2/README")).unwrap(), "upper\n");
+    assert!(!base.join("dst2/readme").exists());
+    println!("  [OK] Entries differing only in case refused");
+}
+
+#[test]
+fn test_preserve_flags() {
+    let base = TestDir::new("recursive_copy_test_preserve_flags");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("locked.txt"), "new");
+    create_file(&src.join("logs/app.log"), "log");
+    create_file(&dst.join("locked.txt"), "old");
+
+    println!("--- Running Test: Preserve Flags ---");
+    // Immutable plus no-dump, which any owner may set.
+    let immutable = sys::FS_IMMUTABLE_FL | 0x40;
+    let append = sys::FS_APPEND_FL;
+    let locked = [(src.join("locked.txt"), immutable), (src.join("logs"), append), (dst.join("locked.txt"), immutable)];
+    for (path, flags) in &locked {
+        if let Err(e) = sys::inode_flags(path).and_then(|old| sys::set_inode_flags(path, old | flags)) {
+            println!("  [SKIP] Inode flags unavailable: {e}");
+            return;
+        }
+    }
+
+    let opts = CopyOptions::builder().content_only(true).preserve_flags(true).overwrite(true).build();
+    let res = copy_recursive(&src, &dst, &opts);
+    let flags = |path: &Path| sys::inode_flags(path).map(|flags| flags & sys::COPIED_FLAGS).ok();
+    let found = (flags(&dst.join("locked.txt")), flags(&dst.join("logs")));
+    // Unlocked before asserting so the directories can be removed.
+    for path in [src.join("locked.txt"), src.join("logs"), dst.join("locked.txt"), dst.join("logs")] {
+        if let Ok(old) = sys::inode_flags(&path) {
+            let _ = sys::set_inode_flags(&path, old & !(immutable | append));
+        }
+    }
+    res.unwrap();
+    assert_eq!(found, (Some(immutable), Some(append)));
+    assert_eq!(fs::read_to_string(dst.join("locked.txt")).unwrap(), "new\n");
+    assert_eq!(fs::read_to_string(dst.join("logs/app.log")).unwrap(), "log\n");
+    println!("  [OK] Immutable destination replaced, flags copied");
+}
+
+#[test]
+fn test_newer_than() {
+    use std::time::{Duration, SystemTime};
+
+    let base = TestDir::new("recursive_copy_test_newer_than");
+    let src = base.join("src");
+    create_file(&src.join("old.log"), "old");
+    create_file(&src.join("old/older.log"), "older");
+    create_file(&src.join("deep/er/new.log"), "new");
+    let since = SystemTime::now() - Duration::from_secs(60);
+    for name in ["old.log", "old/older.log"] {
+        let file = File::options().write(true).open(src.join(name)).unwrap();
+        file.set_modified(since).unwrap();
+    }
+
+    println!("--- Running Test: Newer Than ---");
+    let opts = CopyOptions::builder().content_only(true).newer_than(since).prune_empty_dirs(true).build();
+    let stats = copy_recursive(&src, &base.join("dst"), &opts).unwrap();
+    assert_eq!((stats.files_copied, stats.files_skipped), (1, 2));
+    assert_eq!(fs::read_to_string(base.join("dst/deep/er/new.log")).unwrap(), "new\n");
+    assert!(!base.join("dst/old.log").exists());
+    assert!(!base.join("dst/old").exists());
+    println!("  [OK] Only files modified after the threshold copied");
+}
+
+#[test]
+fn test_copy_many() {
+    use std::os::unix::fs::MetadataExt;
+
+    let base = TestDir::new("recursive_copy_test_copy_many");
+    let (a, b) = (base.join("a"), base.join("b.txt"));
+    create_file(&a.join("inner.txt"), "inner");
+    create_file(&b, "b");
+    fs::hard_link(&b, a.join("link.txt")).unwrap();
+    let dst = base.join("out/dest");
+
+    println!("--- Running Test: Copy Many ---");
+    let opts = CopyOptions::builder().preserve_hardlinks(true).build();
+    let stats = copy_many(&[b.clone(), a.clone()], &dst, &opts).unwrap();
+    assert_eq!(fs::read_to_string(dst.join("a/inner.txt")).unwrap(), "inner\n");
+    assert_eq!(fs::read_to_string(dst.join("b.txt")).unwrap(), "b\n");
+    assert_eq!((stats.files_copied, stats.hardlinks_created), (2, 1));
+    assert_eq!(fs::metadata(dst.join("b.txt")).unwrap().ino(), fs::metadata(dst.join("a/link.txt")).unwrap().ino());
+    assert_eq!(stats.destination, dst);
+
+    let opts = CopyOptions { continue_on_error: true, overwrite: true, ..Default::default() };
+    let stats = copy_many(&[base.join("missing"), b.clone()], &dst, &opts).unwrap();
+    assert_eq!(stats.files_copied, 1);
+    assert_eq!(stats.errors.len(), 1);
+    assert!(matches!(stats.errors[0], (ref path, CopyError::SrcNotFound(_)) if *path == base.join("missing")));
+
+    let err = copy_many(std::slice::from_ref(&a), &b, &CopyOptions::default()).unwrap_err().error;
+    assert!(matches!(err, CopyError::DestNotDir(_)));
+    println!("  [OK] Sources copied into one directory");
+}
+
+#[test]
+fn test_content_only_file_source() {
+    let base = TestDir::new("recursive_copy_test_content_only_file");
+    let (file, moved) = (base.join("file.txt"), base.join("moved.txt"));
+    create_file(&file, "file");
+    create_file(&moved, "moved");
+    let dir = base.join("dir");
+    fs::create_dir_all(&dir).unwrap();
+
+    println!("--- Running Test: content_only With A File Source ---");
+    let opts = CopyOptions { content_only: true, ..Default::default() };
+    let stats = copy_recursive(&file, &dir, &opts).unwrap();
+    assert_eq!(stats.destination, dir.join("file.txt"));
+    let stats = copy_recursive(&file, &base.join("renamed.txt"), &opts).unwrap();
+    assert_eq!(stats.destination, base.join("renamed.txt"));
+    move_recursive(&moved, &dir, &opts).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("moved.txt")).unwrap(), "moved\n");
+    println!("  [OK] File copied as itself");
+}
+
+#[test]
+fn test_dest_mode() {
+    let base = TestDir::new("recursive_copy_test_dest_mode");
+    let src = base.join("tree");
+    create_file(&src.join("a.txt"), "a");
+    create_file(&base.join("file.txt"), "file");
+    let existing = base.join("existing");
+    create_file(&existing.join("old.txt"), "old");
+
+    println!("--- Running Test: Destination Mode ---");
+    let merge = CopyOptions { dest_mode: DestMode::As, ..Default::default() };
+    let stats = copy_recursive(&src, &existing, &merge).unwrap();
+    assert_eq!(stats.destination, existing);
+    assert!(existing.join("a.txt").exists() && existing.join("old.txt").exists());
+    let err = copy_recursive(&base.join("file.txt"), &existing, &merge).unwrap_err().error;
+    assert_eq!(err.kind(), CopyErrorKind::Io(io::ErrorKind::IsADirectory));
+    println!("  [OK] As merges into an existing directory");
+
+    let into = CopyOptions { dest_mode: DestMode::Into, content_only: true, ..Default::default() };
+    let stats = copy_recursive(&src, &base.join("new"), &into).unwrap();
+    assert_eq!(stats.destination, base.join("new/tree"));
+    assert!(base.join("new/tree/a.txt").exists());
+    let stats = copy_recursive(&base.join("file.txt"), &base.join("new"), &into).unwrap();
+    assert_eq!(stats.destination, base.join("new/file.txt"));
+    let err = copy_recursive(&src, &base.join("file.txt"), &into).unwrap_err().error;
+    assert!(matches!(err, CopyError::DestNotDir(_)));
+
+    let stats = copy_many(&[src.clone(), base.join("file.txt")], &base.join("many"), &merge).unwrap();
+    assert!(base.join("many/a.txt").exists() && base.join("many/file.txt").exists());
+    assert_eq!(stats.files_copied, 2);
+    println!("  [OK] Into always creates the subdirectory");
+}
+
+#[test]
+fn test_new_file_mode_while_copying() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Mutex;
+
+    let base = TestDir::new("recursive_copy_test_new_file_mode");
+    let src = base.join("secret.key");
+    let dst = base.join("copy.key");
+    create_file(&src, "secret");
+    fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+
+    println!("--- Running Test: New File Mode While Copying ---");
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let (found, watched) = (Arc::clone(&seen), dst.clone());
+    let opts = CopyOptions::builder()
+        .progress(move |event| {
+            if let ProgressEvent::FileStarted { .. } = event {
+                found.lock().unwrap().push(fs::metadata(&watched).unwrap().permissions().mode() & 0o777);
+            }
+        })
+        .build();
+    copy_recursive(&src, &dst, &opts).unwrap();
+    // The umask may narrow, but never widen, the mode of the new file.
+    let modes = seen.lock().unwrap().clone();
+    assert_eq!(modes.len(), 1);
+    assert_eq!(modes[0] & !0o640, 0);
+    assert_eq!(fs::metadata(&dst).unwrap().permissions().mode() & 0o777, 0o640);
+    println!("  [OK] Destination never more open than the source");
+}
+
+#[test]
+fn test_copy_file_stream() {
+    let base = TestDir::new("recursive_copy_test_copy_file_stream");
+    let src = base.join("data.bin");
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 256) as u8).collect();
+    fs::create_dir_all(&*base).unwrap();
+    fs::write(&src, &data).unwrap();
+
+    println!("--- Running Test: Copy File Stream ---");
+    let mut out = Vec::new();
+    let written = copy_file_stream(&src, &mut out, &CopyOptions::builder().buffer_size(8192).build()).unwrap();
+    assert_eq!(written, data.len() as u64);
+    assert_eq!(out, data);
+
+    let err = copy_file_stream(&base, &mut Vec::new(), &CopyOptions::default()).unwrap_err();
+    assert!(matches!(err, CopyError::NotSupported(_)));
+    let err = copy_file_stream(&base.join("missing"), &mut Vec::new(), &CopyOptions::default()).unwrap_err();
+    assert!(matches!(err, CopyError::SrcNotFound(_)));
+    println!("  [OK] File streamed into a Vec<u8>");
+}
+
+#[test]
+fn test_dedup() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let base = TestDir::new("recursive_copy_test_dedup");
+    let src = base.join("src");
+    let dst = base.join("dst");
+    create_file(&src.join("a/lib.so"), "artifact");
+    create_file(&src.join("b/lib.so"), "artifact");
+    create_file(&src.join("c/lib.so"), "artefact");
+
+    println!("--- Running Test: Dedup ---");
+    let opts = CopyOptions::builder().content_only(true).dedup(true).build();
+    let stats = copy_recursive(&src, &dst, &opts).unwrap();
+    let ino = |name: &str| fs::metadata(dst.join(name)).unwrap().ino();
+    assert_eq!(ino("a/lib.so"), ino("b/lib.so"));
+    assert_ne!(ino("a/lib.so"), ino("c/lib.so"));
+    assert_eq!(fs::read_to_string(dst.join("c/lib.so")).unwrap(), "artefact\n");
+    assert_eq!((stats.files_copied, stats.files_deduplicated), (2, 1));
+    println!("  [OK] Identical files linked, others copied");
+
+    let modes = base.join("modes");
+    create_file(&modes.join("a.sh"), "same bytes");
+    create_file(&modes.join("b.txt"), "same bytes");
+    fs::set_permissions(modes.join("a.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(modes.join("b.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+    let opts = CopyOptions { preserve_mode: true, ..opts };
+    let stats = copy_recursive(&modes, &base.join("modes_dst"), &opts).unwrap();
+    let mode = |name: &str| fs::metadata(base.join("modes_dst").join(name)).unwrap().mode() & 0o777;
+    assert_eq!((mode("a.sh"), mode("b.txt")), (0o755, 0o600));
+    assert_eq!((stats.files_copied, stats.files_deduplicated), (2, 0));
+    println!("  [OK] Files with other modes copied");
+}
+
+#[test]
+fn test_overwrite_in_place() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let base = TestDir::new("recursive_copy_test_overwrite_in_place");
+    let src = base.join("config.txt");
+    let dst = base.join("dst/config.txt");
+    create_file(&src, "new contents");
+    create_file(&dst, "old");
+    fs::set_permissions(&dst, fs::Permissions::from_mode(0o600)).unwrap();
+    fs::hard_link(&dst, base.join("dst/alias.txt")).unwrap();
+    let ino = fs::metadata(&dst).unwrap().ino();
+
+    println!("--- Running Test: Overwrite In Place ---");
+    let opts = CopyOptions::builder().overwrite(true).overwrite_in_place(true).build();
+    copy_recursive(&src, &dst, &opts).unwrap();
+    let meta = fs::metadata(&dst).unwrap();
+    assert_eq!((meta.ino(), meta.permissions().mode() & 0o777), (ino, 0o600));
+    assert_eq!(fs::read_to_string(base.join("dst/alias.txt")).unwrap(), "new contents\n");
+
+    copy_recursive(&src, &dst, &CopyOptions::builder().overwrite(true).build()).unwrap();
+    assert_ne!(fs::metadata(&dst).unwrap().ino(), ino);
+    println!("  [OK] Destination rewritten without a new inode");
+}
+
+#[test]
+fn test_same_file() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_same_file");
+    let src = base.join("src");
+    create_file(&src.join("data.txt"), "data");
+    symlink(".", src.join("self")).unwrap();
+
+    println!("--- Running Test: Same File ---");
+    let opts = CopyOptions::builder().overwrite(true).build();
+    let err = copy_recursive(&src.join("data.txt"), &src.join("data.txt"), &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::SameFile(_)));
+    fs::hard_link(src.join("data.txt"), base.join("alias.txt")).unwrap();
+    let err = copy_recursive(&src.join("data.txt"), &base.join("alias.txt"), &opts).unwrap_err().error;
+    assert!(matches!(err, CopyError::SameFile(_)));
+    let contents = CopyOptions { content_only: true, ..opts.clone() };
+    let err = copy_recursive(&src, &src.join("self"), &contents).unwrap_err().error;
+    assert!(matches!(err, CopyError::SameFile(_)));
+
+    // Inside the walk, a symlink at the destination leads back to its source.
+    let dst = base.join("dst");
+    fs::create_dir_all(&dst).unwrap();
+    symlink(src.join("data.txt"), dst.join("data.txt")).unwrap();
+    let tolerant = CopyOptions { continue_on_error: true, ..contents };
+    let stats = copy_recursive(&src, &dst, &tolerant).unwrap();
+    let kinds: Vec<_> = stats.errors.iter().map(|(_, e)| e.kind()).collect();
+    assert_eq!(kinds, [CopyErrorKind::SameFile]);
+    assert_eq!(fs::read_to_string(src.join("data.txt")).unwrap(), "data\n");
+
+    let linked = CopyOptions::builder().link_instead_of_copy(true).overwrite(true).build();
+    let stats = copy_recursive(&src.join("data.txt"), &base.join("alias.txt"), &linked).unwrap();
+    assert_eq!(stats.files_skipped, 1);
+    println!("  [OK] Copies onto the source refused");
+
+    // Without `overwrite` nothing would be written, so a hard-linked backup
+    // tree can be copied over again.
+    let backup = base.join("backup");
+    fs::create_dir_all(&backup).unwrap();
+    fs::hard_link(src.join("data.txt"), backup.join("data.txt")).unwrap();
+    let kept = CopyOptions { content_only: true, restrict_symlinks: true, ..Default::default() };
+    let stats = copy_recursive(&src, &backup, &kept).unwrap();
+    assert_eq!(stats.files_skipped, 1);
+    let stats = copy_recursive(&src.join("data.txt"), &base.join("alias.txt"), &CopyOptions::default()).unwrap();
+    assert_eq!((stats.files_copied, stats.files_skipped), (0, 1));
+    assert_eq!(fs::read_to_string(src.join("data.txt")).unwrap(), "data\n");
+    println!("  [OK] Hard links to the source skipped when not overwriting");
+}
+
+#[cfg(feature = "no-follow-symlinks")]
+#[test]
+fn test_no_follow_symlinks_feature() {
+    use std::os::unix::fs::symlink;
+
+    let base = TestDir::new("recursive_copy_test_no_follow");
+    let src = base.join("src");
+    create_file(&src.join("sub/data.txt"), "data");
+    symlink("sub", src.join("dir_link")).unwrap();
+    symlink("sub/data.txt", src.join("file_link")).unwrap();
+
+    println!("--- Running Test: no-follow-symlinks Feature ---");
+    let opts = CopyOptions { follow_symlinks: true, content_only: true, ..Default::default() };
+    let dst = base.join("dst");
+    let stats = copy_recursive(&src, &dst, &opts).expect("Copy failed");
+    assert_eq!(fs::read_link(dst.join("dir_link")).unwrap(), PathBuf::from("sub"));
+    assert_eq!(fs::read_link(dst.join("file_link")).unwrap(), PathBuf::from("sub/data.txt"));
+    assert_eq!((stats.files_copied, stats.symlinks_created), (1, 2));
+    println!("  [OK] Links recreated although follow_symlinks was set");
+
+    let top = base.join("top");
+    let stats = copy_recursive(&src.join("dir_link"), &top, &CopyOptions::default()).expect("Copy failed");
+    assert_eq!(fs::read_link(&top).unwrap(), PathBuf::from("sub"));
+    assert_eq!((stats.files_copied, stats.symlinks_created), (0, 1));
+    let streamed = copy_file_stream(&src.join("file_link"), &mut Vec::new(), &CopyOptions::default());
+    assert!(matches!(streamed, Err(CopyError::NotSupported(_))));
+    println!("  [OK] Top-level link recreated although follow_top_level_symlink was set");
+}
@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use walkdir_minimal::WalkDir;
+
+use crate::filter::Filter;
+use crate::hash;
+use crate::options::{DestMode, HashAlgo, VerifyMode};
+use crate::sink::{CopySink, copy_recursive_to_sink};
+use crate::{CopyError, CopyOptions, buffer_len};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    Missing(PathBuf),
+    Extra(PathBuf),
+    Kind(PathBuf),
+    Size { path: PathBuf, expected: u64, found: u64 },
+    Content { path: PathBuf, expected: String, found: String },
+    LinkTarget { path: PathBuf, expected: PathBuf, found: PathBuf },
+}
+
+// Fed the source tree by the copy walk, and checks each entry against its
+// counterpart under `dst`.
+struct Checker {
+    dst: PathBuf,
+    single: bool,
+    algo: Option<HashAlgo>,
+    buf: Vec<u8>,
+    seen: HashSet<PathBuf>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Checker {
+    // The destination entry for `path`, unless it is missing or of another
+    // kind, which is recorded instead.
+    fn counterpart(&mut self, path: &Path, is_kind: fn(&fs::FileType) -> bool) -> io::Result<Option<fs::Metadata>> {
+        self.seen.insert(path.to_path_buf());
+        match fs::symlink_metadata(self.target(path)) {
+            Ok(meta) if is_kind(&meta.file_type()) => Ok(Some(meta)),
+            Ok(_) => {
+                self.mismatches.push(Mismatch::Kind(path.to_path_buf()));
+                Ok(None)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.mismatches.push(Mismatch::Missing(path.to_path_buf()));
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn target(&self, path: &Path) -> PathBuf {
+        if self.single { self.dst.clone() } else { self.dst.join(path) }
+    }
+}
+
+impl CopySink for Checker {
+    fn on_dir(&mut self, path: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+        self.counterpart(path, fs::FileType::is_dir)?;
+        Ok(())
+    }
+
+    fn on_file(
+        &mut self,
+        path: &Path,
+        meta: &fs::Metadata,
+        contents: &mut dyn Read,
+    ) -> io::Result<()> {
+        let Some(dst_meta) = self.counterpart(path, fs::FileType::is_file)? else {
+            return Ok(());
+        };
+        if dst_meta.len() != meta.len() {
+            self.mismatches.push(Mismatch::Size { path: path.to_path_buf(), expected: meta.len(), found: dst_meta.len() });
+            return Ok(());
+        }
+        let Some(algo) = &self.algo else {
+            return Ok(());
+        };
+        let mut hasher = hash::hasher(algo);
+        loop {
+            match contents.read(&mut self.buf) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&self.buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        let expected = hash::finish(hasher);
+        let found = hash::hash_file(&self.target(path), algo, &mut self.buf)?;
+        if found != expected {
+            self.mismatches.push(Mismatch::Content { path: path.to_path_buf(), expected, found });
+        }
+        Ok(())
+    }
+
+    fn on_symlink(&mut self, path: &Path, target: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+        if self.counterpart(path, fs::FileType::is_symlink)?.is_none() {
+            return Ok(());
+        }
+        let found = fs::read_link(self.target(path))?;
+        if found != target {
+            self.mismatches.push(Mismatch::LinkTarget { path: path.to_path_buf(), expected: target.to_path_buf(), found });
+        }
+        Ok(())
+    }
+}
+
+// `src` and `dst` are the roots of the two trees, e.g. `CopyStats::destination`
+// after a copy, so the paths reported are relative to both.
+pub fn verify_tree(src: &Path, dst: &Path, opts: &CopyOptions) -> Result<Vec<Mismatch>, CopyError> {
+    let opts = CopyOptions { dest_mode: DestMode::As, max_files: None, ..opts.clone() };
+    let single = if opts.follows_top_level_symlink() {
+        !src.is_dir()
+    } else {
+        !fs::symlink_metadata(src).is_ok_and(|m| m.is_dir())
+    };
+    let algo = match &opts.verify {
+        VerifyMode::Checksum(algo) => Some(algo.clone()),
+        _ => None,
+    };
+    let mut checker = Checker {
+        dst: dst.to_path_buf(),
+        single,
+        buf: vec![0; if algo.is_some() { buffer_len(&opts) } else { 0 }],
+        algo,
+        seen: HashSet::new(),
+        mismatches: Vec::new(),
+    };
+    copy_recursive_to_sink(src, &mut checker, &opts)?;
+    let Checker { seen, mut mismatches, .. } = checker;
+    if single || fs::symlink_metadata(dst).is_err() {
+        return Ok(mismatches);
+    }
+
+    // Entries the walk leaves out on purpose (excluded, ignored) are not
+    // extra, and special files are never handed to it.
+    let extra = Rc::new(RefCell::new(Vec::new()));
+    let found = Rc::clone(&extra);
+    let filter = Filter::new(&opts);
+    let dst_root = dst.to_path_buf();
+    let walker = WalkDir::new(dst)?.filter_entry(move |e| {
+        let rel = e.path().strip_prefix(&dst_root).unwrap_or(e.path());
+        let special = e.file_type().is_ok_and(|t| !t.is_dir() && !t.is_file() && !t.is_symlink());
+        if special
+            || filter.is_excluded(rel)
+            || opts.respect_ignore_files && filter.is_ignored(rel, e.file_type().is_ok_and(|t| t.is_dir()))
+        {
+            return false;
+        }
+        if !seen.contains(rel) {
+            found.borrow_mut().push(Mismatch::Extra(rel.to_path_buf()));
+            return false;
+        }
+        true
+    });
+    for entry in walker {
+        entry?;
+    }
+    mismatches.extend(extra.take());
+    Ok(mismatches)
+}